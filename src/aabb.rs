@@ -0,0 +1,122 @@
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        // corners may come in any order
+        Aabb {
+            minimum: Vec3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            maximum: Vec3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    //smallest box containing both
+    pub fn surrounding(a: Aabb, b: Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(
+                a.minimum.x().min(b.minimum.x()),
+                a.minimum.y().min(b.minimum.y()),
+                a.minimum.z().min(b.minimum.z()),
+            ),
+            Vec3::new(
+                a.maximum.x().max(b.maximum.x()),
+                a.maximum.y().max(b.maximum.y()),
+                a.maximum.z().max(b.maximum.z()),
+            ),
+        )
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    // 0 = x, 1 = y, 2 = z
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    //slab test: intersect the t-intervals of the three axis slabs
+    //returns the part of [t_min, t_max] inside the box
+    pub fn intersect(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.minimum[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit() {
+        let bbox = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let toward = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let away = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let beside = Ray::new(Point3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(bbox.hit(&toward, 0.001, f64::INFINITY));
+        assert!(!bbox.hit(&away, 0.001, f64::INFINITY));
+        assert!(!bbox.hit(&beside, 0.001, f64::INFINITY));
+        assert!(!bbox.hit(&toward, 0.001, 3.0));
+    }
+
+    #[test]
+    fn test_axis_parallel_ray() {
+        // direction components of 0 give infinite slab bounds
+        let bbox = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let r = Ray::new(Point3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(bbox.hit(&r, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn test_surrounding() {
+        let a = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let b = Aabb::new(Point3::new(2.0, -1.0, 0.5), Point3::new(3.0, 0.0, 4.0));
+        let s = Aabb::surrounding(a, b);
+        assert!((s.min().y() - (-1.0)).abs() < f64::EPSILON);
+        assert!((s.max().x() - 3.0).abs() < f64::EPSILON);
+        assert!((s.max().z() - 4.0).abs() < f64::EPSILON);
+        assert!((a.surface_area() - 6.0).abs() < f64::EPSILON);
+        assert_eq!(s.longest_axis(), 2);
+    }
+}
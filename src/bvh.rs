@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, World};
+use super::ray::Ray;
+
+// SAH costs, relative to one primitive intersection
+const TRAVERSAL_COST: f64 = 0.125;
+const SAH_BUCKETS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+
+pub enum BvhNode {
+    Leaf {
+        objects: World,
+        bbox: Option<Aabb>,
+    },
+    Branch {
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+        bbox: Aabb,
+    },
+    //objects without a bounding box, e.g. planes, are tried by every ray
+    Unbounded {
+        objects: World,
+        bounded: Box<BvhNode>,
+    },
+}
+
+struct Primitive {
+    object: Arc<dyn Hit>,
+    bbox: Aabb,
+    centroid: f64,
+}
+
+impl BvhNode {
+    /// Objects without a bounding box stay outside the tree and are
+    /// tested against every ray.
+    pub fn new(objects: World) -> BvhNode {
+        let mut unbounded = World::new();
+        let mut prims = Vec::new();
+        for object in objects {
+            match object.bounding_box() {
+                Some(bbox) => prims.push(Primitive {
+                    object,
+                    bbox,
+                    centroid: 0.0,
+                }),
+                None => unbounded.push(object),
+            }
+        }
+        let bounded = Self::build(prims);
+        if unbounded.is_empty() {
+            bounded
+        } else {
+            BvhNode::Unbounded {
+                objects: unbounded,
+                bounded: Box::new(bounded),
+            }
+        }
+    }
+
+    fn build(mut prims: Vec<Primitive>) -> BvhNode {
+        if prims.is_empty() {
+            return BvhNode::Leaf {
+                objects: World::new(),
+                bbox: None,
+            };
+        }
+
+        let bbox = prims
+            .iter()
+            .skip(1)
+            .fold(prims[0].bbox, |acc, p| Aabb::surrounding(acc, p.bbox));
+        if prims.len() == 1 {
+            return Self::leaf(prims, bbox);
+        }
+
+        //bounds of the centroids decide the split axis and the bucket ranges
+        let first = prims[0].bbox.centroid();
+        let centroid_bounds = prims
+            .iter()
+            .skip(1)
+            .fold(Aabb::new(first, first), |acc, p| {
+                let c = p.bbox.centroid();
+                Aabb::surrounding(acc, Aabb::new(c, c))
+            });
+        let axis = centroid_bounds.longest_axis();
+        let lo = centroid_bounds.min()[axis];
+        let extent = centroid_bounds.max()[axis] - lo;
+        for p in prims.iter_mut() {
+            p.centroid = p.bbox.centroid()[axis];
+        }
+
+        //all centroids coincide, no split can separate them
+        if extent <= 0.0 {
+            if prims.len() <= MAX_LEAF_SIZE {
+                return Self::leaf(prims, bbox);
+            }
+            let right = prims.split_off(prims.len() / 2);
+            return Self::branch(prims, right, bbox);
+        }
+
+        let bucket_of =
+            |c: f64| (((c - lo) / extent * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1);
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds: [Option<Aabb>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+        for p in prims.iter() {
+            let b = bucket_of(p.centroid);
+            counts[b] += 1;
+            bounds[b] = Some(bounds[b].map_or(p.bbox, |acc| Aabb::surrounding(acc, p.bbox)));
+        }
+
+        // cost of splitting after bucket i:
+        // C = C_trav + (A_left * N_left + A_right * N_right) / A_parent
+        let area = bbox.surface_area();
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        for i in 0..SAH_BUCKETS - 1 {
+            let (mut n0, mut n1) = (0, 0);
+            let (mut b0, mut b1): (Option<Aabb>, Option<Aabb>) = (None, None);
+            for j in 0..=i {
+                n0 += counts[j];
+                b0 = merge(b0, bounds[j]);
+            }
+            for j in i + 1..SAH_BUCKETS {
+                n1 += counts[j];
+                b1 = merge(b1, bounds[j]);
+            }
+            if n0 == 0 || n1 == 0 {
+                continue;
+            }
+            let a0 = b0.map_or(0.0, |b| b.surface_area());
+            let a1 = b1.map_or(0.0, |b| b.surface_area());
+            let cost = if area > 0.0 {
+                TRAVERSAL_COST + (a0 * n0 as f64 + a1 * n1 as f64) / area
+            } else {
+                TRAVERSAL_COST + prims.len() as f64 / 2.0
+            };
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        let leaf_cost = prims.len() as f64;
+        if prims.len() <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+            return Self::leaf(prims, bbox);
+        }
+
+        let (left, right): (Vec<Primitive>, Vec<Primitive>) = prims
+            .into_iter()
+            .partition(|p| bucket_of(p.centroid) <= best_split);
+        Self::branch(left, right, bbox)
+    }
+
+    fn leaf(prims: Vec<Primitive>, bbox: Aabb) -> BvhNode {
+        BvhNode::Leaf {
+            objects: prims.into_iter().map(|p| p.object).collect(),
+            bbox: Some(bbox),
+        }
+    }
+
+    fn branch(left: Vec<Primitive>, right: Vec<Primitive>, bbox: Aabb) -> BvhNode {
+        BvhNode::Branch {
+            left: Box::new(Self::build(left)),
+            right: Box::new(Self::build(right)),
+            bbox,
+        }
+    }
+}
+
+fn merge(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Aabb::surrounding(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+impl Hit for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        match self {
+            BvhNode::Leaf { objects, bbox } => match bbox {
+                Some(bbox) if bbox.hit(r, t_min, t_max) => objects.hit(r, t_min, t_max),
+                _ => None,
+            },
+            BvhNode::Branch { left, right, bbox } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return None;
+                }
+                let hit_left = left.hit(r, t_min, t_max);
+                let closest = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+                let hit_right = right.hit(r, t_min, closest);
+                hit_right.or(hit_left)
+            }
+            BvhNode::Unbounded { objects, bounded } => {
+                let hit_unbounded = objects.hit(r, t_min, t_max);
+                let closest = hit_unbounded.as_ref().map_or(t_max, |rec| rec.t);
+                bounded.hit(r, t_min, closest).or(hit_unbounded)
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self {
+            BvhNode::Leaf { objects, bbox } => match bbox {
+                Some(bbox) if bbox.hit(r, t_min, t_max) => objects.transmittance(r, t_min, t_max),
+                _ => 1.0,
+            },
+            BvhNode::Branch { left, right, bbox } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return 1.0;
+                }
+                let tr = left.transmittance(r, t_min, t_max);
+                if tr == 0.0 {
+                    return 0.0;
+                }
+                tr * right.transmittance(r, t_min, t_max)
+            }
+            BvhNode::Unbounded { objects, bounded } => {
+                let tr = objects.transmittance(r, t_min, t_max);
+                if tr == 0.0 {
+                    return 0.0;
+                }
+                tr * bounded.transmittance(r, t_min, t_max)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            BvhNode::Leaf { bbox, .. } => *bbox,
+            BvhNode::Branch { bbox, .. } => Some(*bbox),
+            BvhNode::Unbounded { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::{Color, Point3, Vec3};
+
+    fn grid_world(n: usize) -> World {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = World::new();
+        for i in 0..n {
+            for j in 0..n {
+                let center = Point3::new(i as f64, j as f64, -10.0 - ((i * 7 + j * 3) % 5) as f64);
+                world.push(Arc::new(Sphere::new(center, 0.3, mat.clone())));
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        let world = grid_world(20);
+        let bvh = BvhNode::new(world.clone());
+        for k in 0..200 {
+            let dir = Vec3::new(
+                (k % 20) as f64 * 0.05 - 0.1,
+                (k / 10) as f64 * 0.05 - 0.1,
+                -1.0,
+            );
+            let r = Ray::new(Point3::new(0.3, 0.7, 0.0), dir, 0.0);
+            let expect = world.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let got = bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            match (expect, got) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9),
+                (None, None) => {}
+                _ => panic!("bvh and linear scan disagree for ray {}", k),
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh = BvhNode::new(World::new());
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(bvh.hit(&r, 0.001, f64::INFINITY).is_none());
+        assert!(bvh.bounding_box().is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use super::hit::Hit;
+use super::random::random_range;
 use super::ray::Ray;
 use super::vec::{Point3, Vec3};
 
@@ -6,17 +8,28 @@ pub struct Camera {
     lower_left_corner: Point3,
     horizontal: Point3,
     vertical: Point3,
+    cu: Vec3,
+    cv: Vec3,
+    cw: Vec3,
+    lens_radius: f64,
+    //shutter open and close times
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
     /// vfov：垂直视场角
     /// aspect_ratio：长宽比
+    /// aperture：镜头直径，0 为针孔相机
+    /// focus_dist：到清晰平面的距离
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
         vup: Vec3,
         vfov: f64,
         aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
     ) -> Camera {
         const FOCAL_LENGTH: f64 = 1.0;
 
@@ -29,25 +42,137 @@ impl Camera {
         let cu = vup.cross(cw).normalized();
         let cv = cw.cross(cu);
 
-        let h = viewport_width * cu;
-        let v = viewport_height * cv;
+        //the viewport lies on the focus plane
+        let h = focus_dist * viewport_width * cu;
+        let v = focus_dist * viewport_height * cv;
 
-        let llc = lookfrom - h / 2.0 - v / 2.0 - cw;
+        let llc = lookfrom - h / 2.0 - v / 2.0 - focus_dist * cw;
 
         Camera {
             origin: lookfrom,
             lower_left_corner: llc,
             horizontal: h,
             vertical: v,
+            cu,
+            cv,
+            cw,
+            lens_radius: aperture / 2.0,
+            time0: 0.0,
+            time1: 0.0,
+        }
+    }
+
+    /// Rays get times in [time0, time1), the shutter is instantaneous by default.
+    pub fn with_shutter(self, time0: f64, time1: f64) -> Camera {
+        Camera {
+            time0,
+            time1,
+            ..self
         }
     }
 
     //input s,t in 0 to 1, output a ray
     //s,t of viewport
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+        //random point on the lens
+        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+        let offset = rd.x() * self.cu + rd.y() * self.cv;
+        let time = if self.time1 > self.time0 {
+            random_range(self.time0..self.time1)
+        } else {
+            self.time0
+        };
+        Ray::new(
+            self.origin + offset,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
+        )
+    }
+
+    /// Deterministic ray for `get_ray`'s s,t: from the lens center at
+    /// mid-shutter, as a pinhole camera would see it.
+    pub fn get_center_ray(&self, s: f64, t: f64) -> Ray {
         Ray::new(
             self.origin,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+            0.5 * (self.time0 + self.time1),
+        )
+    }
+
+    /// Distance along the view direction to the first hit through the
+    /// image center, for use as `focus_dist`.
+    pub fn autofocus(&self, world: &dyn Hit) -> Option<f64> {
+        let center = self.lower_left_corner + 0.5 * self.horizontal + 0.5 * self.vertical;
+        let r = Ray::new(self.origin, center - self.origin, self.time0);
+        let rec = world.hit(&r, 0.001, f64::INFINITY)?;
+        Some((rec.p - self.origin).dot((-1.0) * self.cw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::hit::World;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::Color;
+
+    fn camera(aperture: f64, focus_dist: f64) -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            aperture,
+            focus_dist,
         )
     }
+
+    #[test]
+    fn test_rays_meet_on_the_focus_plane() {
+        let cam = camera(0.5, 3.0);
+        for _ in 0..10 {
+            let r = cam.get_ray(0.25, 0.75);
+            assert!(r.origin().z().abs() < 1e-12);
+            // every lens sample passes through the same point at z = -3
+            let p = r.at(-3.0 / r.direction().z());
+            assert!((p.x() - -3.0).abs() < 1e-9);
+            assert!((p.y() - 1.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_shutter() {
+        let cam = camera(0.0, 1.0).with_shutter(1.0, 1.5);
+        for _ in 0..10 {
+            let time = cam.get_ray(0.5, 0.5).time();
+            assert!((1.0..1.5).contains(&time));
+        }
+        assert_eq!(camera(0.0, 1.0).get_ray(0.5, 0.5).time(), 0.0);
+    }
+
+    #[test]
+    fn test_center_ray() {
+        let cam = camera(0.5, 3.0).with_shutter(1.0, 1.5);
+        let r = cam.get_center_ray(0.25, 0.75);
+        assert!(r.origin().length() < 1e-12 && r.time() == 1.25);
+        // through the same focus point as the lens samples
+        let p = r.at(-3.0 / r.direction().z());
+        assert!((p.x() - -3.0).abs() < 1e-9 && (p.y() - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_autofocus() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let world: World = vec![Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -5.0),
+            1.0,
+            material,
+        ))];
+        assert!((camera(0.0, 1.0).autofocus(&world).unwrap() - 4.0).abs() < 1e-9);
+        assert!(camera(0.0, 1.0).autofocus(&World::new()).is_none());
+    }
 }
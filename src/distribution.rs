@@ -0,0 +1,145 @@
+/// Piecewise-constant density over [0, 1) with `func.len()` equal steps.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// `func` must not be empty or negative. An all-zero function samples uniformly.
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        assert!(!func.is_empty());
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    fn density(&self, i: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[i] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    /// Maps a uniform `u` in [0, 1) to (x, pdf(x), index of the step holding x).
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.func.len();
+        //last step whose cdf starts at or below u
+        let offset = (self.cdf.partition_point(|&c| c <= u) - 1).min(n - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            ((u - self.cdf[offset]) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let x = ((offset as f64 + du) / n as f64).min(1.0 - f64::EPSILON);
+        (x, self.density(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let n = self.func.len();
+        let i = ((x * n as f64) as usize).min(n - 1);
+        self.density(i)
+    }
+}
+
+/// Piecewise-constant density over [0, 1)^2 given as a row-major grid,
+/// sampled through the marginal density of the rows and the conditional
+/// density within a row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Distribution2D {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Point (x along a row, y across rows) and its density.
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample(u1);
+        let (x, pdf_x, _) = self.conditional[row].sample(u0);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let height = self.conditional.len();
+        let row = ((y * height as f64) as usize).min(height - 1);
+        self.marginal.pdf(y) * self.conditional[row].pdf(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_1d() {
+        let d = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert!((d.integral() - 2.0).abs() < 1e-12);
+        // cdf steps at 0, 1/8, 1/2, 1/2, 1
+        let (x, pdf, i) = d.sample(0.3);
+        assert_eq!(i, 1);
+        assert!((pdf - 1.5).abs() < 1e-12);
+        assert!((x - (1.0 + 0.175 / 0.375) / 4.0).abs() < 1e-12);
+        // the empty step is never chosen
+        assert_eq!(d.sample(0.5).2, 3);
+        assert_eq!(d.pdf(0.6), 0.0);
+        assert!(d.sample(0.999999).0 < 1.0);
+    }
+
+    #[test]
+    fn test_zero_function_is_uniform() {
+        let d = Distribution1D::new(vec![0.0; 4]);
+        let (x, pdf, _) = d.sample(0.3);
+        assert!((x - 0.3).abs() < 1e-12);
+        assert!((pdf - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sample_2d() {
+        let func = [1.0, 2.0, 0.0, 5.0, 0.0, 0.0];
+        let d = Distribution2D::new(&func, 3, 2);
+        // the density integrates to one over the grid cells
+        let total: f64 = (0..6)
+            .map(|k| d.pdf((k % 3) as f64 / 3.0 + 0.1, (k / 3) as f64 / 2.0 + 0.1) / 6.0)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        for (u0, u1) in [(0.1, 0.2), (0.7, 0.9), (0.5, 0.5)] {
+            let ((x, y), pdf) = d.sample(u0, u1);
+            assert!(pdf > 0.0);
+            assert!((pdf - d.pdf(x, y)).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::f64::consts::PI;
+
+use super::distribution::Distribution2D;
+use super::input::{Image, Wrap};
+use super::random::random_double;
+use super::vec::{Color, Vec3};
+
+/// Radiance arriving from infinitely far away, seen by rays that miss the scene.
+pub trait Environment: Send + Sync {
+    /// `direction` does not have to be unit length.
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    /// A unit direction towards the bright parts and its solid angle pdf.
+    /// `None` when the environment is smooth enough for BSDF sampling alone.
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    /// Solid angle pdf of `sample` returning `direction`.
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Environment for SolidColor {
+    fn radiance(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Blends from `bottom` straight down to `top` going up. As in the original
+/// sky the blend runs past `top` above the horizon.
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Gradient {
+        Gradient { bottom, top }
+    }
+
+    /// The white to light blue sky of the book.
+    pub fn sky() -> Gradient {
+        Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let unit_direction = direction.normalized();
+        let t = 0.75 * (unit_direction.y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// Equirectangular (latitude-longitude) map: +y is the top row and -z
+/// the center of the image. Importance sampled by pixel luminance.
+pub struct EnvironmentMap {
+    image: Image,
+    intensity: f64,
+    //radians about +y
+    rotation: f64,
+    //over (u, 1 - v), rows weighted by sin(theta) for the smaller solid angle near the poles
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image, intensity: f64, rotation: f64) -> EnvironmentMap {
+        let mut func = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            let sin_theta = (PI * (y as f64 + 0.5) / image.height as f64).sin();
+            for x in 0..image.width {
+                let c = image.pixel(x, y);
+                let luminance = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+                func.push(luminance.max(0.0) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, image.width, image.height);
+        EnvironmentMap {
+            image,
+            intensity,
+            rotation,
+            distribution,
+        }
+    }
+
+    /// Image coordinates in [0, 1]^2 of a direction, v up.
+    pub fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let d = direction.normalized();
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let phi = d.x().atan2(-d.z()) - self.rotation;
+        let u = (0.5 + phi / (2.0 * PI)).rem_euclid(1.0);
+        (u, 1.0 - theta / PI)
+    }
+
+    /// Unit direction through image coordinates (u, v).
+    pub fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let theta = (1.0 - v) * PI;
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        self.intensity * self.image.bilinear(u, v, Wrap::Repeat, Wrap::Clamp)
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        let ((u, y), pdf) = self.distribution.sample(random_double(), random_double());
+        let sin_theta = (y * PI).sin();
+        if pdf == 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        //the map covers 2pi by pi radians
+        let pdf = pdf / (2.0 * PI * PI * sin_theta);
+        Some((self.uv_to_direction(u, 1.0 - y), pdf))
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_uv_round_trip() {
+        let image = Image {
+            width: 1,
+            height: 1,
+            pixels: vec![Color::new(1.0, 1.0, 1.0)],
+        };
+        let map = EnvironmentMap::new(image, 1.0, 0.7);
+        for (u, v) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.75), (0.3, 0.99)] {
+            let (u2, v2) = map.direction_to_uv(map.uv_to_direction(u, v));
+            assert!((u - u2).abs() < 1e-9 && (v - v2).abs() < 1e-9);
+        }
+        let map = EnvironmentMap::new(map.image, 1.0, 0.0);
+        let (u, v) = map.direction_to_uv(Vec3::new(0.0, 0.0, -1.0));
+        assert!((u - 0.5).abs() < 1e-12 && (v - 0.5).abs() < 1e-12);
+        assert!((map.direction_to_uv(Vec3::new(0.0, 2.0, 0.0)).1 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_map_lookup() {
+        // top half red, bottom half blue
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let image = Image {
+            width: 4,
+            height: 2,
+            pixels: vec![red, red, red, red, blue, blue, blue, blue],
+        };
+        let map = EnvironmentMap::new(image, 2.0, 0.0);
+        let up = map.radiance(Vec3::new(0.1, 1.0, 0.0));
+        assert!((up.x() - 2.0).abs() < 1e-12 && up.z().abs() < 1e-12);
+        let down = map.radiance(Vec3::new(0.0, -1.0, 0.3));
+        assert!((down.z() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sampling_finds_the_sun() {
+        let mut pixels = vec![Color::new(0.01, 0.01, 0.01); 16 * 8];
+        pixels[2 * 16 + 11] = Color::new(1000.0, 1000.0, 1000.0);
+        let image = Image {
+            width: 16,
+            height: 8,
+            pixels,
+        };
+        let map = EnvironmentMap::new(image, 1.0, 0.3);
+        let mut near_sun = 0;
+        let n = 20000;
+        for _ in 0..n {
+            let (direction, pdf) = map.sample().unwrap();
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            assert!((pdf - map.pdf(direction)).abs() < 1e-6 * pdf);
+            let (u, v) = map.direction_to_uv(direction);
+            if (u * 16.0) as usize == 11 && ((1.0 - v) * 8.0) as usize == 2 {
+                near_sun += 1;
+            }
+        }
+        assert!(near_sun > n * 9 / 10);
+
+        // the pdf integrates to one over the sphere, dw = 2pi^2 sin(theta) du dv
+        let (nu, nv) = (64, 32);
+        let mut total = 0.0;
+        for j in 0..nv {
+            for i in 0..nu {
+                let u = (i as f64 + 0.5) / nu as f64;
+                let v = (j as f64 + 0.5) / nv as f64;
+                let dw = 2.0 * PI * PI * (v * PI).sin() / (nu * nv) as f64;
+                total += map.pdf(map.uv_to_direction(u, v)) * dw;
+            }
+        }
+        assert!((total - 1.0).abs() < 0.01);
+    }
+}
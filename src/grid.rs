@@ -0,0 +1,127 @@
+//! Dense voxel grids of density for heterogeneous media.
+
+use std::{fs, io, path::Path};
+
+use super::input::invalid_data;
+use super::vec::Point3;
+
+/// Densities at `nx * ny * nz` voxel centers, x varying fastest.
+pub struct DensityGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f32>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(resolution: [usize; 3], data: Vec<f32>) -> io::Result<DensityGrid> {
+        let [nx, ny, nz] = resolution;
+        if nx == 0 || ny == 0 || nz == 0 {
+            return Err(invalid_data("grid resolution must be positive"));
+        }
+        if data.len() != nx * ny * nz {
+            return Err(invalid_data(format!(
+                "expected {} voxels for {}x{}x{}, found {}",
+                nx * ny * nz,
+                nx,
+                ny,
+                nz,
+                data.len()
+            )));
+        }
+        if data.iter().any(|d| !d.is_finite() || *d < 0.0) {
+            return Err(invalid_data("densities must be finite and not negative"));
+        }
+        let max = data.iter().fold(0.0f32, |m, &d| m.max(d)) as f64;
+        Ok(DensityGrid {
+            nx,
+            ny,
+            nz,
+            data,
+            max,
+        })
+    }
+
+    /// Headerless little-endian f32 values, x varying fastest, then y, then z.
+    pub fn load_raw(path: &Path, resolution: [usize; 3]) -> io::Result<DensityGrid> {
+        let bytes = fs::read(path)?;
+        if bytes.len() % 4 != 0 {
+            return Err(invalid_data("raw grid size is not a multiple of 4 bytes"));
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        DensityGrid::new(resolution, data)
+    }
+
+    /// Largest density anywhere, the majorant for tracking.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.data[(z * self.ny + y) * self.nx + x] as f64
+    }
+
+    /// Trilinear lookup at `p` in [0, 1]^3, clamped to the outer voxel centers.
+    pub fn lookup(&self, p: Point3) -> f64 {
+        //index and weight of the lower neighbour along one axis
+        let axis = |t: f64, n: usize| {
+            let s = (t * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (s.floor() as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), s - i as f64)
+        };
+        let (x0, x1, fx) = axis(p.x(), self.nx);
+        let (y0, y1, fy) = axis(p.y(), self.ny);
+        let (z0, z1, fz) = axis(p.z(), self.nz);
+
+        let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+        let plane = |z| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), fx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), fx),
+                fy,
+            )
+        };
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trilinear_lookup() {
+        // 2x1x1: 0 on the left, 4 on the right
+        let grid = DensityGrid::new([2, 1, 1], vec![0.0, 4.0]).unwrap();
+        assert_eq!(grid.max(), 4.0);
+        assert!((grid.lookup(Point3::new(0.5, 0.5, 0.5)) - 2.0).abs() < 1e-12);
+        assert!((grid.lookup(Point3::new(0.375, 0.1, 0.9)) - 1.0).abs() < 1e-12);
+        // flat past the outer voxel centers
+        assert_eq!(grid.lookup(Point3::new(0.1, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.lookup(Point3::new(1.2, 0.5, 0.5)), 4.0);
+
+        let grid = DensityGrid::new([1, 1, 1], vec![3.0]).unwrap();
+        assert_eq!(grid.lookup(Point3::new(0.7, 0.2, 0.4)), 3.0);
+        assert!(DensityGrid::new([2, 2, 1], vec![1.0; 3]).is_err());
+        assert!(DensityGrid::new([1, 1, 1], vec![-1.0]).is_err());
+    }
+
+    #[test]
+    fn test_load_raw() {
+        let path = std::env::temp_dir().join("density_grid_test.raw");
+        let bytes: Vec<u8> = [0.5f32, 1.0, 1.5, 2.0]
+            .iter()
+            .flat_map(|d| d.to_le_bytes())
+            .collect();
+        fs::write(&path, bytes).unwrap();
+        let grid = DensityGrid::load_raw(&path, [2, 2, 1]).unwrap();
+        // x fastest
+        assert_eq!(grid.lookup(Point3::new(0.0, 1.0, 0.5)), 1.5);
+        assert!(DensityGrid::load_raw(&path, [2, 2, 2]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
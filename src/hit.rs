@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use super::aabb::Aabb;
 use super::material::Scatter;
 use super::ray::Ray;
 use super::vec::{Point3, Vec3};
@@ -8,15 +9,33 @@ pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
     pub t: f64,
+    //surface coordinates
+    pub u: f64,
+    pub v: f64,
+    //(b1, b2) of a triangle hit, b0 = 1 - b1 - b2
+    pub barycentric: Option<(f64, f64)>,
     pub front_face: bool,
     pub material: Arc<dyn Scatter>,
 }
 
 pub trait Hit: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    //None for objects without finite extent
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Fraction of light getting through along `r` between `t_min` and
+    /// `t_max`, for shadow rays. Surfaces block everything, media override
+    /// this with an estimate of exp(-optical depth).
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.hit(r, t_min, t_max).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
 }
 impl HitRecord {
-    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) -> () {
+    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         // if OP dot AP <0, light source outside od sphere
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         if self.front_face {
@@ -27,7 +46,7 @@ impl HitRecord {
     }
 }
 
-pub type World = Vec<Arc<dyn Hit + Sync>>;
+pub type World = Vec<Arc<dyn Hit>>;
 
 impl Hit for World {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
@@ -43,4 +62,25 @@ impl Hit for World {
 
         tmp_rec
     }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut tr = 1.0;
+        for object in self {
+            tr *= object.transmittance(r, t_min, t_max);
+            if tr == 0.0 {
+                break;
+            }
+        }
+        tr
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |acc, object| {
+            object
+                .bounding_box()
+                .map(|bbox| Aabb::surrounding(acc, bbox))
+        })
+    }
 }
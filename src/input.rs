@@ -0,0 +1,415 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use super::tonemap::srgb_decode;
+use super::vec::Color;
+
+/// Linear color per pixel, top row first.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+/// What a lookup does outside [0, 1].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    //repeat, flipping every other copy
+    Mirror,
+}
+
+impl Wrap {
+    fn index(self, i: isize, n: usize) -> usize {
+        match self {
+            Wrap::Repeat => i.rem_euclid(n as isize) as usize,
+            Wrap::Clamp => i.clamp(0, n as isize - 1) as usize,
+            Wrap::Mirror => {
+                let n = n as isize;
+                let m = i.rem_euclid(2 * n);
+                (if m < n { m } else { 2 * n - 1 - m }) as usize
+            }
+        }
+    }
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinear lookup, `u` to the right and `v` up, pixel centers at
+    /// (i + 0.5) / size.
+    pub fn bilinear(&self, u: f64, v: f64, wrap_u: Wrap, wrap_v: Wrap) -> Color {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let xs = [x0 as isize, x0 as isize + 1].map(|i| wrap_u.index(i, self.width));
+        let ys = [y0 as isize, y0 as isize + 1].map(|i| wrap_v.index(i, self.height));
+
+        let top = (1.0 - fx) * self.pixel(xs[0], ys[0]) + fx * self.pixel(xs[1], ys[0]);
+        let bottom = (1.0 - fx) * self.pixel(xs[0], ys[1]) + fx * self.pixel(xs[1], ys[1]);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+pub(crate) fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Picks the format from the extension: .hdr, .exr, .png or .jpg.
+/// PNG and JPEG are taken to be sRGB encoded and converted to linear.
+pub fn load_image(path: &Path) -> io::Result<Image> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("hdr") => read_radiance(&mut BufReader::new(fs::File::open(path)?)),
+        Some("exr") => read_exr(path),
+        Some("png") => read_png(BufReader::new(fs::File::open(path)?)),
+        Some("jpg" | "jpeg") => read_jpeg(BufReader::new(fs::File::open(path)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unsupported image format '{}', expected .hdr, .exr, .png or .jpg",
+                path.display()
+            ),
+        )),
+    }
+}
+
+//gray or color samples with optional alpha, which is dropped
+fn from_srgb_samples(width: usize, height: usize, channels: usize, samples: &[f64]) -> Image {
+    let pixels = samples
+        .chunks(channels)
+        .map(|s| match channels {
+            1 | 2 => {
+                let l = srgb_decode(s[0]);
+                Color::new(l, l, l)
+            }
+            _ => Color::new(srgb_decode(s[0]), srgb_decode(s[1]), srgb_decode(s[2])),
+        })
+        .collect();
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+fn read_png(input: impl BufRead + io::Seek) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(input);
+    //palettes to RGB, low bit depths to 8
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| invalid_data(err.to_string()))?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| invalid_data("PNG image too large"))?;
+    let mut buf = vec![0; size];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|err| invalid_data(err.to_string()))?;
+    let buf = &buf[..info.buffer_size()];
+
+    let channels = info.color_type.samples();
+    let samples: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => buf
+            .chunks(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / u16::MAX as f64)
+            .collect(),
+        _ => buf.iter().map(|&b| b as f64 / u8::MAX as f64).collect(),
+    };
+    Ok(from_srgb_samples(
+        info.width as usize,
+        info.height as usize,
+        channels,
+        &samples,
+    ))
+}
+
+fn read_jpeg(input: impl Read) -> io::Result<Image> {
+    let mut decoder = jpeg_decoder::Decoder::new(input);
+    let data = decoder
+        .decode()
+        .map_err(|err| invalid_data(err.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| invalid_data("jpeg has no header"))?;
+    let (channels, samples): (usize, Vec<f64>) = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => (1, data.iter().map(|&b| b as f64 / 255.0).collect()),
+        jpeg_decoder::PixelFormat::L16 => (
+            1,
+            data.chunks(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / u16::MAX as f64)
+                .collect(),
+        ),
+        jpeg_decoder::PixelFormat::RGB24 => (3, data.iter().map(|&b| b as f64 / 255.0).collect()),
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            return Err(invalid_data("CMYK JPEG images are not supported"))
+        }
+    };
+    Ok(from_srgb_samples(
+        info.width as usize,
+        info.height as usize,
+        channels,
+        &samples,
+    ))
+}
+
+fn read_exr(path: &Path) -> io::Result<Image> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| Image {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![Color::new(0.0, 0.0, 0.0); resolution.area()],
+        },
+        |image, pos, (r, g, b, _a): (f32, f32, f32, f32)| {
+            let width = image.width;
+            image.pixels[pos.y() * width + pos.x()] = Color::new(r as f64, g as f64, b as f64);
+        },
+    )
+    .map_err(|err| invalid_data(err.to_string()))?;
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+//16k x 16k, far past any environment map
+const MAX_RADIANCE_PIXELS: usize = 1 << 28;
+
+/// Radiance RGBE, flat or run-length encoded scanlines, `-Y H +X W` only.
+pub fn read_radiance(input: &mut dyn BufRead) -> io::Result<Image> {
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+    //header ends at the first empty line
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unexpected end of header"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("unsupported format {}", format)));
+            }
+        }
+    }
+
+    line.clear();
+    input.read_line(&mut line)?;
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (height, width) = match tokens[..] {
+        ["-Y", h, "+X", w] => (
+            h.parse::<usize>().map_err(|_| invalid_data("bad height"))?,
+            w.parse::<usize>().map_err(|_| invalid_data("bad width"))?,
+        ),
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported resolution line '{}'",
+                line.trim()
+            )))
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(invalid_data("image has no pixels"));
+    }
+    //the header alone decides the allocation, so keep it within reason
+    let size = width
+        .checked_mul(height)
+        .filter(|&size| size <= MAX_RADIANCE_PIXELS)
+        .ok_or_else(|| invalid_data(format!("image of {} x {} is too large", width, height)))?;
+    let mut pixels = Vec::with_capacity(size);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(input, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&p| from_rgbe(p)));
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn read_scanline(input: &mut dyn BufRead, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    input.read_exact(&mut first)?;
+    let is_rle = (8..0x8000).contains(&width)
+        && first[0] == 2
+        && first[1] == 2
+        && ((first[2] as usize) << 8 | first[3] as usize) == width;
+    if !is_rle {
+        scanline[0] = first;
+        for pixel in scanline[1..].iter_mut() {
+            input.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    //each channel is stored separately as runs and literal spans
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            input.read_exact(&mut count)?;
+            let (count, run) = if count[0] > 128 {
+                (count[0] as usize - 128, true)
+            } else {
+                (count[0] as usize, false)
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("bad run length in scanline"));
+            }
+            if run {
+                let mut value = [0u8; 1];
+                input.read_exact(&mut value)?;
+                for pixel in scanline[x..x + count].iter_mut() {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                for pixel in scanline[x..x + count].iter_mut() {
+                    let mut value = [0u8; 1];
+                    input.read_exact(&mut value)?;
+                    pixel[channel] = value[0];
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+fn from_rgbe(p: [u8; 4]) -> Color {
+    if p[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(p[3] as i32 - (128 + 8));
+    let c = |x: u8| (x as f64 + 0.5) * f;
+    Color::new(c(p[0]), c(p[1]), c(p[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{writer_for_path, BitDepth, Framebuffer, WriterOptions};
+
+    #[test]
+    fn test_radiance_round_trip() {
+        let image = Framebuffer {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                Color::new(0.0, 0.0, 0.0),
+                Color::new(1.0, 0.5, 0.25),
+                Color::new(100.0, 3.0, 0.01),
+                Color::new(0.001, 0.002, 0.003),
+            ],
+            extra_channels: Vec::new(),
+        };
+        let mut out = Vec::new();
+        writer_for_path(Path::new("a.hdr"), &WriterOptions::default())
+            .unwrap()
+            .write(&image, &mut out)
+            .unwrap();
+
+        let read = read_radiance(&mut &out[..]).unwrap();
+        assert_eq!((read.width, read.height), (2, 2));
+        for (a, b) in image.pixels.iter().zip(read.pixels.iter()) {
+            let max = a.x().max(a.y()).max(a.z());
+            // 8-bit mantissa relative to the brightest channel
+            assert!((*a - *b).length() <= max / 64.0);
+        }
+    }
+
+    #[test]
+    fn test_rle_scanline() {
+        let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        data.extend([2, 2, 0, 8]);
+        // red: run of 8, green: 8 literals, blue: two runs of 4, exponent: run of 8
+        data.extend([128 + 8, 128]);
+        data.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        data.extend([128 + 4, 0, 128 + 4, 255]);
+        data.extend([128 + 8, 129]);
+        let image = read_radiance(&mut &data[..]).unwrap();
+        assert_eq!(image.width, 8);
+        let p = image.pixel(5, 0);
+        assert!((p.x() - 128.5 / 128.0).abs() < 1e-12);
+        assert!((p.y() - 80.5 / 128.0).abs() < 1e-12);
+        assert!((p.z() - 255.5 / 128.0).abs() < 1e-12);
+        assert!((image.pixel(0, 0).z() - 0.5 / 128.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_radiance_bad_resolution() {
+        for resolution in [
+            "-Y 1 +X 0",
+            "-Y 0 +X 4",
+            "-Y 65536 +X 65536",
+            "-Y 18446744073709551615 +X 2",
+        ] {
+            let data = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution);
+            let err = read_radiance(&mut data.as_bytes()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", resolution);
+        }
+    }
+
+    #[test]
+    fn test_bilinear() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)],
+        };
+        // pixel centers
+        assert!((image.bilinear(0.25, 0.5, Wrap::Repeat, Wrap::Clamp).x()).abs() < 1e-12);
+        assert!((image.bilinear(0.75, 0.5, Wrap::Repeat, Wrap::Clamp).x() - 1.0).abs() < 1e-12);
+        assert!((image.bilinear(0.5, 0.5, Wrap::Repeat, Wrap::Clamp).x() - 0.5).abs() < 1e-12);
+        // the left edge blends with the right column only when repeating
+        assert!((image.bilinear(0.0, 0.5, Wrap::Repeat, Wrap::Clamp).x() - 0.5).abs() < 1e-12);
+        assert!((image.bilinear(0.0, 0.5, Wrap::Clamp, Wrap::Clamp).x()).abs() < 1e-12);
+        // mirrored the column left of the edge is the left column again
+        assert!((image.bilinear(0.0, 0.5, Wrap::Mirror, Wrap::Clamp).x()).abs() < 1e-12);
+        assert!((image.bilinear(1.25, 0.5, Wrap::Mirror, Wrap::Clamp).x() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let image = Framebuffer {
+            width: 2,
+            height: 1,
+            pixels: vec![Color::new(0.0, 0.5, 1.0), Color::new(0.2, 0.2, 0.2)],
+            extra_channels: Vec::new(),
+        };
+        let options = WriterOptions {
+            bit_depth: BitDepth::Sixteen,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        writer_for_path(Path::new("a.png"), &options)
+            .unwrap()
+            .write(&image, &mut out)
+            .unwrap();
+
+        // the writer encodes sRGB, reading decodes it again
+        let read = read_png(io::Cursor::new(out)).unwrap();
+        assert_eq!((read.width, read.height), (2, 1));
+        for (a, b) in image.pixels.iter().zip(read.pixels.iter()) {
+            assert!((*a - *b).length() < 1e-3);
+        }
+    }
+}
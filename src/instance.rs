@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+use super::transform::Transform;
+
+/// A shared object placed in the world by `transform`, so one mesh can be
+/// drawn many times without copying it. Rays are taken into object space
+/// and hits brought back out.
+pub struct Transformed<H: Hit + ?Sized> {
+    object: Arc<H>,
+    //object to world
+    transform: Transform,
+    //replaces the object's own materials when set
+    material: Option<Arc<dyn Scatter>>,
+    bbox: Option<Aabb>,
+}
+
+/// Any object behind a transform.
+pub type Instance = Transformed<dyn Hit>;
+
+impl<H: Hit + ?Sized> Transformed<H> {
+    pub fn new(object: Arc<H>, transform: Transform) -> Transformed<H> {
+        let bbox = object
+            .bounding_box()
+            .map(|bbox| transform.bounding_box(bbox));
+        Transformed {
+            object,
+            transform,
+            material: None,
+            bbox,
+        }
+    }
+
+    pub fn with_material(mut self, m: Arc<dyn Scatter>) -> Transformed<H> {
+        self.material = Some(m);
+        self
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl<H: Hit + ?Sized> Hit for Transformed<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        //t means the same in both spaces, see Transform::ray
+        let local = self.transform.inverse().ray(r);
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+        rec.p = self.transform.point(rec.p);
+        //the inverse transpose keeps the sign of d·n, so front_face holds
+        rec.normal = self.transform.normal(rec.normal).normalized();
+        if let Some(m) = &self.material {
+            rec.material = m.clone();
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.object
+            .transmittance(&self.transform.inverse().ray(r), t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::hit::World;
+    use crate::material::Lambertian;
+    use crate::mesh::{MeshData, MeshFace, TriangleMesh};
+    use crate::sphere::Sphere;
+    use crate::vec::{Color, Point3, Vec3};
+
+    fn grey() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_squashed_sphere() {
+        // unit sphere stretched to 2 along x and moved to z = -5
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey()));
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .unwrap()
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let instance = Transformed::new(sphere, transform);
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let rec = instance
+            .hit(
+                &Ray::new(origin, Vec3::new(1.5, 0.0, -5.0), 0.0),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        // on the ellipsoid x^2/4 + y^2 + (z + 5)^2 = 1
+        let q = rec.p - Point3::new(0.0, 0.0, -5.0);
+        assert!((q.x() * q.x() / 4.0 + q.y() * q.y() + q.z() * q.z() - 1.0).abs() < 1e-9);
+        let gradient = Vec3::new(q.x() / 4.0, q.y(), q.z()).normalized();
+        assert!((rec.normal - gradient).length() < 1e-9 && rec.front_face);
+        assert!(
+            (rec.p - Ray::new(origin, Vec3::new(1.5, 0.0, -5.0), 0.0).at(rec.t)).length() < 1e-9
+        );
+
+        // the far side, from inside
+        let rec = instance
+            .hit(
+                &Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0), 0.0),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9 && !rec.front_face);
+        assert!((rec.normal.x() + 1.0).abs() < 1e-9);
+
+        let bbox = instance.bounding_box().unwrap();
+        assert!((bbox.max().x() - 2.0).abs() < 1e-9 && (bbox.min().z() + 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_many_instances_share_a_mesh() {
+        // one triangle facing +z, placed on a 40 x 40 grid with its own material each
+        let mesh = Arc::new(TriangleMesh::new(MeshData {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![],
+            uvs: vec![],
+            faces: vec![MeshFace {
+                vertices: [0, 1, 2],
+                normals: None,
+                uvs: None,
+                material: 0,
+            }],
+            materials: vec![grey()],
+        }));
+        let mut materials = Vec::new();
+        let mut world = World::new();
+        for i in 0..40 {
+            for j in 0..40 {
+                let m: Arc<dyn Scatter> =
+                    Arc::new(Lambertian::new(Color::new(i as f64, j as f64, 0.0)));
+                let transform = Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 90.0).then(
+                    &Transform::translate(Vec3::new(2.0 * i as f64, 2.0 * j as f64, -1.0)),
+                );
+                world.push(Arc::new(
+                    Transformed::new(mesh.clone(), transform).with_material(m.clone()),
+                ));
+                materials.push(m);
+            }
+        }
+        assert_eq!(Arc::strong_count(&mesh), 1 + 40 * 40);
+        let bvh = BvhNode::new(world);
+
+        // turned a quarter, the triangle at (2i, 2j) covers x in [2i - 1, 2i]
+        let r = Ray::new(Point3::new(13.7, 20.2, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = bvh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-9 && rec.front_face);
+        assert!(Arc::ptr_eq(&rec.material, &materials[7 * 40 + 10]));
+        let miss = Ray::new(Point3::new(14.2, 20.2, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(bvh.hit(&miss, 0.001, f64::INFINITY).is_none());
+    }
+}
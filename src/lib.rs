@@ -0,0 +1,31 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod distribution;
+pub mod environment;
+pub mod grid;
+pub mod hit;
+pub mod input;
+pub mod instance;
+pub mod light;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
+pub mod output;
+pub mod perlin;
+pub mod plane;
+pub mod poly;
+pub mod quad;
+pub mod quadric;
+pub mod random;
+pub mod ray;
+pub mod render;
+pub mod scene;
+pub mod sphere;
+pub mod texture;
+pub mod tonemap;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
+pub mod vec;
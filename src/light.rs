@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use super::hit::Hit;
+use super::random::random_double;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// An emissive shape that can be aimed at directly.
+pub trait Light: Hit {
+    /// A unit direction from `origin` towards a random point of the shape
+    /// and its solid angle pdf. `None` when nothing can be seen from `origin`.
+    fn sample_light(&self, origin: Point3) -> Option<(Vec3, f64)>;
+
+    /// Solid angle pdf of `sample_light` from `origin` picking `direction`,
+    /// 0 if the ray misses the shape.
+    fn pdf(&self, origin: Point3, direction: Vec3) -> f64;
+}
+
+/// The lights of a scene, each picked with equal chance.
+#[derive(Clone, Default)]
+pub struct LightList {
+    lights: Vec<Arc<dyn Light>>,
+}
+
+impl LightList {
+    pub fn new(lights: Vec<Arc<dyn Light>>) -> LightList {
+        LightList { lights }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// One light picked uniformly, a unit direction towards it, and the
+    /// density of that: the light's own pdf over the number of lights.
+    pub fn sample(&self, origin: Point3) -> Option<(&dyn Light, Vec3, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let n = self.lights.len();
+        let light = self.lights[((random_double() * n as f64) as usize).min(n - 1)].as_ref();
+        let (direction, pdf) = light.sample_light(origin)?;
+        Some((light, direction, pdf / n as f64))
+    }
+
+    /// Density of `sample` choosing `r` when `r` first reaches an emitter at
+    /// `t`: only a light surfacing right there can have been the one sampled.
+    /// Intersects every light, so only ask for it on emitter hits.
+    pub fn pdf(&self, r: &Ray, t: f64) -> f64 {
+        let on_light = |light: &&Arc<dyn Light>| {
+            light
+                .hit(r, 0.001, f64::INFINITY)
+                .is_some_and(|rec| (rec.t - t).abs() <= 1e-9 * t.max(1.0))
+        };
+        match self.lights.iter().find(on_light) {
+            Some(light) => light.pdf(r.origin(), r.direction()) / self.lights.len() as f64,
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use crate::quad::BoxShape;
+    use crate::vec::Color;
+
+    #[test]
+    fn test_pdf_of_the_light_reached() {
+        // a glowing box: a ray through it crosses two sides, but only the
+        // near one can be the light that was sampled
+        let shape = BoxShape::new(
+            Point3::new(-1.0, -1.0, -3.0),
+            Point3::new(1.0, 1.0, -1.0),
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        );
+        let sides: Vec<Arc<dyn Light>> = shape
+            .sides()
+            .iter()
+            .map(|side| side.clone() as Arc<dyn Light>)
+            .collect();
+        let lights = LightList::new(sides.clone());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let r = Ray::new(origin, Vec3::new(0.1, 0.2, -1.0), 0.0);
+        let front = &sides[0];
+        let t = front.hit(&r, 0.001, f64::INFINITY).unwrap().t;
+        let expected = front.pdf(origin, r.direction()) / 6.0;
+        assert!(expected > 0.0 && (lights.pdf(&r, t) - expected).abs() < 1e-12);
+        // an emitter that is not in the list has no light density
+        assert_eq!(lights.pdf(&r, 0.5 * t), 0.0);
+
+        for _ in 0..20 {
+            let (light, direction, pdf) = lights.sample(origin).unwrap();
+            assert!((pdf - light.pdf(origin, direction) / 6.0).abs() < 1e-12);
+        }
+    }
+}
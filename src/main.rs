@@ -1,130 +1,183 @@
 use std::{
-    io::{stderr, Write},
-    sync::{Arc, Mutex},
+    fs::File,
+    io::{stdout, BufWriter, Write},
+    path::{Path, PathBuf},
 };
-mod camera;
-mod hit;
-mod material;
-mod ray;
-mod sphere;
-mod vec;
-use camera::Camera;
-use hit::{Hit, World};
-use rand::Rng;
-use ray::Ray;
-use rayon::prelude::*;
-use sphere::Sphere;
-use vec::{Color, Point3};
 
-use crate::{
-    material::{Dielectric, Lambertian, Metal},
-    vec::Vec3,
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Parser,
+};
+use ray_tracing_in_one_week::{
+    output::{self, BitDepth, ExrPrecision, WriterOptions},
+    render::{render, render_aovs},
+    scene::SceneFile,
+    tonemap::{self, DisplayTransform, TONE_MAPPERS},
 };
-fn ray_color(r: &Ray, world: &World, depth: u64) -> Color {
-    //max depth, set black
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        //hit
-        if let Some((attenuation, scattered)) = rec.material.scatter(r, &rec) {
-            attenuation * ray_color(&scattered, world, depth - 1)
-        } else {
-            Color::new(0.0, 0.0, 0.0)
-        }
-    } else {
-        //no hit, set color
 
-        let unit_direction = r.direction().normalized();
-        let t = 0.75 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-    }
+/// Path tracer from "Ray Tracing in One Weekend".
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Built-in scene to render
+    #[arg(long, default_value = "three_spheres")]
+    scene: String,
+    /// JSON scene description, replaces --scene
+    #[arg(long, conflicts_with = "scene")]
+    scene_file: Option<PathBuf>,
+    /// List the built-in scenes and exit
+    #[arg(long)]
+    list_scenes: bool,
+
+    /// Image width in pixels
+    #[arg(long)]
+    width: Option<u64>,
+    /// Image height in pixels, changes the aspect ratio when given with --width
+    #[arg(long)]
+    height: Option<u64>,
+    /// Samples per pixel
+    #[arg(long)]
+    spp: Option<u64>,
+    /// Maximum number of bounces
+    #[arg(long)]
+    max_depth: Option<u64>,
+    /// Bounces before Russian roulette may end a path
+    #[arg(long)]
+    min_bounces: Option<u64>,
+
+    /// Number of render threads, defaults to one per core
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+    /// Seed for reproducible images
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Output file, .png, .ppm, .exr, .hdr or .pfm; plain-text PPM on stdout when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Bits per channel of the output image
+    #[arg(
+        long,
+        default_value_t = 8,
+        value_parser = PossibleValuesParser::new(["8", "16"]).map(|s| s.parse::<u8>().unwrap())
+    )]
+    bit_depth: u8,
+    /// Tone curve for PNG and PPM output
+    #[arg(long, default_value = "clamp", value_parser = PossibleValuesParser::new(TONE_MAPPERS))]
+    tonemap: String,
+    /// Exposure adjustment in stops, applied before the tone curve
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f64,
+    /// Radiance that maps to white for extended-reinhard and hable
+    #[arg(long, default_value_t = 4.0)]
+    white: f64,
+    /// Store EXR channels as 32-bit float instead of half
+    #[arg(long)]
+    exr_float: bool,
+    /// Add depth and normal channels to EXR output
+    #[arg(long)]
+    aovs: bool,
 }
 
 fn main() {
-    //Image
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: u64 = 256;
-    const IMAGE_HEIGHT: u64 = ((IMAGE_WIDTH as f64) / ASPECT_RATIO) as u64;
-    const SAMPLES_PER_PIXEL: u64 = 1000;
-    const MAX_DEPTH: u64 = 20;
-    //World
-    let r: f64 = (std::f64::consts::PI / 4.0).cos();
-    let mut world = World::new();
-
-    let mat_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let mat_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let mat_left = Arc::new(Dielectric::new(1.5));
-    let mat_left_inner = Arc::new(Dielectric::new(1.5));
-    let mat_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 1.0));
-
-    let sphere_ground = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, mat_ground);
-    let sphere_center = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, mat_center);
-    let sphere_left = Sphere::new(Point3::new(-1.0, 0.0, -1.0), 0.5, mat_left);
-    let sphere_left_inner = Sphere::new(Point3::new(-1.0, 0.0, -1.0), -0.4, mat_left_inner);
-    let sphere_right = Sphere::new(Point3::new(1.0, 0.0, -1.0), 0.5, mat_right);
-
-    world.push(Arc::new(sphere_ground));
-    world.push(Arc::new(sphere_center));
-    world.push(Arc::new(sphere_left));
-    world.push(Arc::new(sphere_left_inner));
-    world.push(Arc::new(sphere_right));
-
-    // Camera
-    let cam = Camera::new(
-        Point3::new(-2.0, 2.0, 1.0),
-        Point3::new(0.0, 0.0, -1.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        20.0,
-        ASPECT_RATIO,
-    );
-
-    //photo
-    println!("P3");
-    println!("{} {}", IMAGE_WIDTH, IMAGE_HEIGHT);
-    println!("255");
-
-    let output_buffer = Arc::new(Mutex::new(vec![
-        Color::new(0.0, 0.0, 0.0);
-        (IMAGE_WIDTH * IMAGE_HEIGHT) as usize
-    ]));
-
-    (0..IMAGE_HEIGHT as usize)
-        .collect::<Vec<usize>>()
-        .par_iter()
-        //.rev()
-        .for_each_with(output_buffer.clone(), |output_buffer, &j| {
-            eprint!(
-                "\r{:5.2}%",
-                (IMAGE_HEIGHT as usize - j) as f64 * 100.0 / IMAGE_HEIGHT as f64
-            );
-            stderr().flush().unwrap();
+    let args = Args::parse();
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
 
-            let mut line_colors = Vec::with_capacity(IMAGE_WIDTH as usize);
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.list_scenes {
+        for name in SceneFile::builtin_names() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
 
-            for i in 0..IMAGE_WIDTH {
-                let mut rng = rand::thread_rng();
-                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..SAMPLES_PER_PIXEL {
-                    let random_u: f64 = rng.gen();
-                    let random_v: f64 = rng.gen();
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
 
-                    let u = ((i as f64) + random_u) / ((IMAGE_WIDTH - 1) as f64);
-                    let v = ((j as f64) + random_v) / ((IMAGE_HEIGHT - 1) as f64);
+    //Scene
+    let (mut file, base_dir) = match &args.scene_file {
+        Some(path) => (
+            SceneFile::load(path)?,
+            path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        ),
+        None => {
+            let file = SceneFile::builtin(&args.scene).ok_or_else(|| {
+                format!(
+                    "unknown scene '{}', expected one of: {}",
+                    args.scene,
+                    SceneFile::builtin_names().join(", ")
+                )
+            })?;
+            (file, PathBuf::new())
+        }
+    };
 
-                    let r = cam.get_ray(u, v);
-                    pixel_color += ray_color(&r, &world, MAX_DEPTH);
-                }
-                line_colors.push(pixel_color);
-            }
-            output_buffer.lock().unwrap()[(IMAGE_HEIGHT as usize - 1 - j) * IMAGE_WIDTH as usize
-                ..(IMAGE_HEIGHT as usize - 1 - j + 1) * IMAGE_WIDTH as usize]
-                .copy_from_slice(&line_colors);
-        });
+    match (args.width, args.height) {
+        (Some(width), Some(height)) => {
+            file.render.image_width = width;
+            file.render.image_height = Some(height);
+            file.camera.aspect_ratio = width as f64 / height as f64;
+        }
+        (Some(width), None) => file.render.image_width = width,
+        (None, Some(height)) => {
+            file.render.image_width = (height as f64 * file.camera.aspect_ratio).round() as u64;
+            file.render.image_height = Some(height);
+        }
+        (None, None) => {}
+    }
+    if let Some(spp) = args.spp {
+        file.render.samples_per_pixel = spp;
+    }
+    if let Some(max_depth) = args.max_depth {
+        file.render.max_depth = max_depth;
+    }
+    if let Some(min_bounces) = args.min_bounces {
+        file.render.min_bounces = min_bounces;
+    }
+    let scene = file.build(&base_dir)?;
 
-    for color in output_buffer.lock().unwrap().iter() {
-        println!("{}", color.format_color(SAMPLES_PER_PIXEL));
+    let options = WriterOptions {
+        bit_depth: if args.bit_depth == 16 {
+            BitDepth::Sixteen
+        } else {
+            BitDepth::Eight
+        },
+        display: DisplayTransform {
+            exposure: args.exposure,
+            tone_mapper: tonemap::tone_mapper_by_name(&args.tonemap, args.white).unwrap(),
+        },
+        exr_precision: if args.exr_float {
+            ExrPrecision::Float
+        } else {
+            ExrPrecision::Half
+        },
+    };
+    //fail on a bad extension before spending time on the render
+    let (writer, mut out): (_, Box<dyn Write>) = match &args.output {
+        Some(path) => (
+            output::writer_for_path(path, &options)?,
+            Box::new(BufWriter::new(File::create(path)?)),
+        ),
+        None => (
+            output::ascii_ppm_writer(&options),
+            Box::new(BufWriter::new(stdout().lock())),
+        ),
+    };
+
+    let mut image = render(&scene, args.seed);
+    if args.aovs {
+        image.extra_channels = render_aovs(&scene);
     }
 
-    eprintln!("\r  Done!  ");
+    //photo
+    writer.write(&image, &mut out)?;
+    out.flush()?;
+
+    Ok(())
 }
@@ -1,58 +1,160 @@
-use rand::Rng;
+use std::sync::Arc;
 
-use crate::vec::Vec3;
+use crate::random::random_double;
+use crate::texture::{SolidColor, Texture};
+use crate::vec::{Point3, Vec3};
 
 use super::{hit::HitRecord, ray::Ray, vec::Color};
 
+/// An outgoing direction picked by `Scatter::sample`.
+pub struct BsdfSample {
+    pub direction: Vec3,
+    /// BSDF times cosine over pdf, what the path throughput is multiplied by.
+    pub weight: Color,
+    /// Solid angle density of `direction`, 0 for delta lobes.
+    pub pdf: f64,
+    /// Mirror or glass: the direction was the only possible one, so `eval`
+    /// and `pdf` are zero everywhere and lights can't be sampled for it.
+    pub is_delta: bool,
+}
+
 pub trait Scatter: Sync + Send {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+    /// Picks a direction to continue a path that arrived along `r_in`,
+    /// `None` if the light is absorbed.
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample>;
+
+    /// BSDF times the cosine at the hit for leaving along `direction`, so
+    /// the integrator can weigh directions it picked itself, e.g. towards
+    /// a light. Black for delta lobes.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Solid angle density with which `sample` picks `direction`.
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Radiance given off at the hit point, black for everything but lights.
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Whether shapes made of this go into the scene's light list.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
-        Lambertian { albedo: a }
+        Self::textured(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
+        Lambertian { albedo }
     }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let target = rec.p + rec.normal + Vec3::random_in_unit_sphere();
-        let scattered = Ray::new(rec.p, target - rec.p);
-        //let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere().normalized();
-        // if scatter_direction.near_zero() {
-        //     scatter_direction = rec.normal;
-        // }
-        // let scattered = Ray::new(rec.p, scatter_direction);
-        Some((self.albedo, scattered))
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        //cosine weighted, so the weight is just the albedo
+        let mut direction = rec.normal + Vec3::random_unit_vector();
+        if direction.near_zero() {
+            direction = rec.normal;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: self.pdf(r_in, rec, direction),
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let cosine = rec.normal.dot(direction.normalized());
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / std::f64::consts::PI
+        }
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(a: Color, f: f64) -> Metal {
+        Self::textured(Arc::new(SolidColor::new(a)), f)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, f: f64) -> Metal {
         Metal {
-            albedo: a,
+            albedo,
             fuzz: if f < 1.0 { f } else { 1.0 },
         }
     }
+
+    //the fuzzed direction is the unit mirror direction plus a uniform point
+    //in a ball of radius fuzz around its tip; the density of `direction` is
+    //the ball's volume along that ray, weighted by r^2 for the solid angle
+    fn fuzz_pdf(&self, reflected: Vec3, direction: Vec3) -> f64 {
+        let d = direction.normalized();
+        let b = d.dot(reflected);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let far = b + discriminant.sqrt();
+        if far <= 0.0 {
+            return 0.0;
+        }
+        let near = (b - discriminant.sqrt()).max(0.0);
+        (far.powi(3) - near.powi(3)) / (4.0 * std::f64::consts::PI * self.fuzz.powi(3))
+    }
 }
-impl Scatter for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let reflected = r_in.direction().reflect(rec.normal);
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
 
-        if scattered.direction().dot(rec.normal) > 0.0 {
-            Some((self.albedo, scattered))
+impl Scatter for Metal {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let reflected = r_in.direction().normalized().reflect(rec.normal);
+        let is_delta = self.fuzz == 0.0;
+        let direction = if is_delta {
+            reflected
         } else {
-            None
+            reflected + self.fuzz * Vec3::random_in_unit_sphere()
+        };
+        //fuzzed below the surface: absorbed
+        if direction.dot(rec.normal) <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: self.pdf(r_in, rec, direction),
+            is_delta,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        if self.fuzz == 0.0 || direction.dot(rec.normal) <= 0.0 {
+            return 0.0;
         }
+        let reflected = r_in.direction().normalized().reflect(rec.normal);
+        self.fuzz_pdf(reflected, direction)
     }
 }
 pub struct Dielectric {
@@ -80,7 +182,7 @@ impl Dielectric {
 }
 
 impl Scatter for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
@@ -90,9 +192,8 @@ impl Scatter for Dielectric {
         let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
+        let will_reflect = random_double() < Self::reflectance(cos_theta, refraction_ratio);
 
         let direction = if cannot_refract || will_reflect {
             //反射（Reflection）
@@ -102,7 +203,231 @@ impl Scatter for Dielectric {
             unit_direction.refract(rec.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(rec.p, direction);
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+        Some(BsdfSample {
+            direction,
+            weight: Color::new(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            is_delta: true,
+        })
+    }
+}
+
+/// Emits `emit` from both sides and absorbs everything that hits it.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn sample(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emit.x() > 0.0 || self.emit.y() > 0.0 || self.emit.z() > 0.0
+    }
+}
+
+/// Phase function of a participating medium, scatters uniformly in all directions.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(a: Color) -> Isotropic {
+        Self::textured(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Scatter for Isotropic {
+    fn sample(&self, _r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            direction: Vec3::random_unit_vector(),
+            weight: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: 1.0 / (4.0 * std::f64::consts::PI),
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+}
+
+/// Henyey-Greenstein phase function, `g` in (-1, 1) is the mean cosine of
+/// the turn: positive scatters forward, negative back, 0 is isotropic.
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(a: Color, g: f64) -> HenyeyGreenstein {
+        Self::textured(Arc::new(SolidColor::new(a)), g)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein { albedo, g }
+    }
+
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * std::f64::consts::PI * denom * denom.sqrt())
+    }
+}
+
+impl Scatter for HenyeyGreenstein {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let g = self.g;
+        let xi = random_double();
+        //inverse of the cdf in cos(theta), measured from the incoming direction
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * random_double();
+
+        //orthonormal basis around the incoming direction
+        let w = r_in.direction().normalized();
+        let (u, v) = w.orthonormal_basis();
+        let direction = sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: self.phase(cos_theta),
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: Vec3) -> f64 {
+        let cos_theta = r_in.direction().normalized().dot(direction.normalized());
+        self.phase(cos_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(normal: Vec3) -> HitRecord {
+        HitRecord {
+            p: Point3::new(0.0, 0.0, 0.0),
+            normal,
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: true,
+            material: Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+        }
+    }
+
+    #[test]
+    fn test_sample_matches_eval_and_pdf() {
+        let rec = record(Vec3::new(0.0, 1.0, 0.0));
+        let r_in = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), 0.0);
+        let albedo = Color::new(0.2, 0.4, 0.8);
+        let materials: Vec<Box<dyn Scatter>> = vec![
+            Box::new(Lambertian::new(albedo)),
+            Box::new(Metal::new(albedo, 0.3)),
+            Box::new(Isotropic::new(albedo)),
+            Box::new(HenyeyGreenstein::new(albedo, 0.5)),
+        ];
+        for material in materials.iter() {
+            for _ in 0..100 {
+                let Some(sample) = material.sample(&r_in, &rec) else {
+                    continue;
+                };
+                assert!(!sample.is_delta && sample.pdf > 0.0);
+                let pdf = material.pdf(&r_in, &rec, sample.direction);
+                assert!((pdf - sample.pdf).abs() < 1e-9 * pdf);
+                let f = material.eval(&r_in, &rec, sample.direction);
+                assert!((f.z() - sample.weight.z() * pdf).abs() < 1e-9 * f.z());
+            }
+        }
+
+        let mirror = Metal::new(albedo, 0.0);
+        let sample = mirror.sample(&r_in, &rec).unwrap();
+        assert!(sample.is_delta);
+        assert!((sample.direction.x() - sample.direction.y()).abs() < 1e-12);
+        assert_eq!(mirror.pdf(&r_in, &rec, sample.direction), 0.0);
+        assert!(Dielectric::new(1.5).sample(&r_in, &rec).unwrap().is_delta);
+    }
+
+    #[test]
+    fn test_fuzz_pdf_integrates_to_one() {
+        // straight down onto the surface no fuzzed direction goes below it
+        let rec = record(Vec3::new(0.0, 1.0, 0.0));
+        let r_in = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        for fuzz in [0.2, 0.6, 1.0] {
+            let metal = Metal::new(Color::new(1.0, 1.0, 1.0), fuzz);
+            // symmetric about the normal, so integrate over cos(theta) only
+            let n = 20000;
+            let total: f64 = (0..n)
+                .map(|i| {
+                    let cos_theta = (i as f64 + 0.5) / n as f64;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let direction = Vec3::new(sin_theta, cos_theta, 0.0);
+                    metal.pdf(&r_in, &rec, direction) * 2.0 * std::f64::consts::PI / n as f64
+                })
+                .sum();
+            assert!((total - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_henyey_greenstein() {
+        let rec = record(Vec3::new(1.0, 0.0, 0.0));
+        let r_in = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.3, 0.0, -2.0), 0.0);
+        for g in [-0.6, 0.0, 0.8] {
+            let hg = HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), g);
+            // the pdf integrates to one over the sphere
+            let n = 2000;
+            let total: f64 = (0..n)
+                .map(|i| {
+                    let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                    hg.phase(cos_theta) * 2.0 * std::f64::consts::PI * 2.0 / n as f64
+                })
+                .sum();
+            assert!((total - 1.0).abs() < 1e-3);
+
+            // and the samples have mean cosine g
+            let samples = 20000;
+            let mut mean = 0.0;
+            for _ in 0..samples {
+                let sample = hg.sample(&r_in, &rec).unwrap();
+                mean += r_in
+                    .direction()
+                    .normalized()
+                    .dot(sample.direction.normalized());
+            }
+            assert!((mean / samples as f64 - g).abs() < 0.02);
+        }
     }
 }
@@ -0,0 +1,281 @@
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::grid::DensityGrid;
+use super::hit::{Hit, HitRecord};
+use super::random::random_double;
+use super::ray::Ray;
+use super::vec::Vec3;
+
+/// Smoke or fog of uniform density filling a closed, convex `boundary`.
+/// A ray scatters at an exponentially distributed distance inside it, so
+/// `hit` is random and thin volumes let most rays through.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hit>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Scatter>,
+}
+
+impl ConstantMedium {
+    /// `density` is the chance of scattering per unit length; `phase_function`
+    /// is usually `Isotropic`.
+    pub fn new(
+        boundary: Arc<dyn Hit>,
+        density: f64,
+        phase_function: Arc<dyn Scatter>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl ConstantMedium {
+    //the part of [t_min, t_max] inside the boundary
+    fn inside(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        //where the whole line enters and leaves, even behind the origin
+        let enter = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, enter.t + 0.0001, f64::INFINITY)?;
+
+        //a ray starting inside begins at t_min
+        let t_enter = enter.t.max(t_min);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.inside(r, t_min, t_max)?;
+        let ray_length = r.direction().length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        //1 - random_double() is in (0, 1], keeping the log finite
+        let hit_distance = self.neg_inv_density * (1.0 - random_double()).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        //there is no surface, normal and front_face are arbitrary
+        Some(HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: true,
+            material: self.phase_function.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+
+    //Beer-Lambert, exact for a constant density
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.inside(r, t_min, t_max) {
+            Some((t_enter, t_exit)) => {
+                let distance_inside = (t_exit - t_enter) * r.direction().length();
+                (distance_inside / self.neg_inv_density).exp()
+            }
+            None => 1.0,
+        }
+    }
+}
+
+/// Smoke with density varying over a voxel grid stretched across `bounds`.
+/// Scattering uses delta tracking and shadow rays ratio tracking, both
+/// against the grid's largest density as majorant.
+pub struct GridMedium {
+    bounds: Aabb,
+    grid: Arc<DensityGrid>,
+    //multiplies the grid values
+    density: f64,
+    phase_function: Arc<dyn Scatter>,
+}
+
+impl GridMedium {
+    pub fn new(
+        bounds: Aabb,
+        grid: Arc<DensityGrid>,
+        density: f64,
+        phase_function: Arc<dyn Scatter>,
+    ) -> GridMedium {
+        GridMedium {
+            bounds,
+            grid,
+            density,
+            phase_function,
+        }
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density * self.grid.max()
+    }
+
+    fn density_at(&self, p: Vec3) -> f64 {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let local = Vec3::new(
+            (p.x() - min.x()) / (max.x() - min.x()),
+            (p.y() - min.y()) / (max.y() - min.y()),
+            (p.z() - min.z()) / (max.z() - min.z()),
+        );
+        self.density * self.grid.lookup(local)
+    }
+
+    //next tentative collision with the majorant, in ray parameter
+    fn step(&self, t: f64, speed: f64) -> f64 {
+        t - (1.0 - random_double()).ln() / (self.majorant() * speed)
+    }
+}
+
+impl Hit for GridMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.bounds.intersect(r, t_min, t_max)?;
+        if self.majorant() <= 0.0 {
+            return None;
+        }
+        let speed = r.direction().length();
+
+        //delta tracking: accept a tentative collision with chance density / majorant
+        let mut t = t_enter;
+        loop {
+            t = self.step(t, speed);
+            if t >= t_exit {
+                return None;
+            }
+            if random_double() * self.majorant() < self.density_at(r.at(t)) {
+                break;
+            }
+        }
+
+        Some(HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: true,
+            material: self.phase_function.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    //ratio tracking: unbiased, and smoother than counting delta tracking misses
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let (t_enter, t_exit) = match self.bounds.intersect(r, t_min, t_max) {
+            Some(interval) => interval,
+            None => return 1.0,
+        };
+        if self.majorant() <= 0.0 {
+            return 1.0;
+        }
+        let speed = r.direction().length();
+
+        let mut tr = 1.0;
+        let mut t = t_enter;
+        loop {
+            t = self.step(t, speed);
+            if t >= t_exit {
+                return tr;
+            }
+            tr *= 1.0 - self.density_at(r.at(t)) / self.majorant();
+            //Russian roulette once little light is left
+            if tr < 0.1 {
+                if random_double() < 0.5 {
+                    return 0.0;
+                }
+                tr *= 2.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Isotropic, Lambertian};
+    use crate::sphere::Sphere;
+    use crate::vec::{Color, Point3};
+
+    fn medium(density: f64) -> ConstantMedium {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(white)),
+        ));
+        ConstantMedium::new(boundary, density, Arc::new(Isotropic::new(white)))
+    }
+
+    #[test]
+    fn test_dense_medium_scatters_at_the_boundary() {
+        let m = medium(1.0e6);
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0), 0.0);
+        let rec = m.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-4);
+        assert!(m.hit(&r, 0.001, 1.5).is_none());
+    }
+
+    #[test]
+    fn test_ray_starting_inside() {
+        let m = medium(1.0e6);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.5), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = m.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!(rec.t >= 0.001 && rec.t < 0.01);
+    }
+
+    #[test]
+    fn test_transmittance() {
+        // a chord of length 2 through density 0.5 lets exp(-1) through
+        let m = medium(0.5);
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let n = 20000;
+        let passed = (0..n)
+            .filter(|_| m.hit(&r, 0.001, f64::INFINITY).is_none())
+            .count();
+        assert!((passed as f64 / n as f64 - (-1.0f64).exp()).abs() < 0.02);
+        assert!((m.transmittance(&r, 0.001, f64::INFINITY) - (-1.0f64).exp()).abs() < 1e-9);
+        assert!((m.transmittance(&r, 0.001, 5.0) - (-0.5f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_grid_medium() {
+        // density 0 on the left half of a unit box, 2 on the right
+        let grid = DensityGrid::new([4, 1, 1], vec![0.0, 0.0, 2.0, 2.0]).unwrap();
+        let bounds = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let phase = Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0)));
+        let m = GridMedium::new(bounds, Arc::new(grid), 1.0, phase);
+
+        // through the empty side nothing is in the way
+        let empty = Ray::new(Point3::new(0.1, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(m.transmittance(&empty, 0.001, f64::INFINITY), 1.0);
+        assert!(m.hit(&empty, 0.001, f64::INFINITY).is_none());
+
+        // through the dense side ratio tracking averages to exp(-2)
+        let dense = Ray::new(Point3::new(0.9, 0.5, -1.0), Vec3::new(0.0, 0.0, 2.0), 0.0);
+        let n = 20000;
+        let mean = (0..n)
+            .map(|_| m.transmittance(&dense, 0.001, f64::INFINITY))
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean - (-2.0f64).exp()).abs() < 0.01);
+        let passed = (0..n)
+            .filter(|_| m.hit(&dense, 0.001, f64::INFINITY).is_none())
+            .count();
+        assert!((passed as f64 / n as f64 - (-2.0f64).exp()).abs() < 0.02);
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::bvh::BvhNode;
+use super::hit::{Hit, HitRecord, World};
+use super::ray::Ray;
+use super::triangle;
+use super::vec::{Point3, Vec3};
+
+/// Indices into the vertex buffers of a `MeshData`.
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+/// Vertex buffers shared by all triangles of a mesh.
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<Arc<dyn Scatter>>,
+}
+
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: BvhNode,
+}
+
+//one face of the mesh, only an index into the shared data
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl TriangleMesh {
+    /// Panics if a face index is out of range; the loaders check indices
+    /// before they get here, so only they build meshes.
+    pub(crate) fn new(data: MeshData) -> TriangleMesh {
+        for face in data.faces.iter() {
+            assert!(face.vertices.iter().all(|&i| i < data.positions.len()));
+            assert!(face
+                .normals
+                .is_none_or(|n| n.iter().all(|&i| i < data.normals.len())));
+            assert!(face
+                .uvs
+                .is_none_or(|t| t.iter().all(|&i| i < data.uvs.len())));
+            assert!(face.material < data.materials.len());
+        }
+
+        let data = Arc::new(data);
+        let triangles: World = (0..data.faces.len())
+            .map(|face| {
+                Arc::new(MeshTriangle {
+                    mesh: data.clone(),
+                    face,
+                }) as Arc<dyn Hit>
+            })
+            .collect();
+        TriangleMesh {
+            data,
+            bvh: BvhNode::new(triangles),
+        }
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.data.faces.len()
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Point3; 3] {
+        let face = &self.mesh.faces[self.face];
+        face.vertices.map(|i| self.mesh.positions[i])
+    }
+}
+
+impl Hit for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let vertices = self.vertices();
+        let hit = triangle::intersect(r, vertices, t_min, t_max, false)?;
+        let face = &self.mesh.faces[self.face];
+        Some(triangle::hit_record(
+            r,
+            vertices,
+            face.normals.map(|n| n.map(|i| self.mesh.normals[i])),
+            face.uvs.map(|t| t.map(|i| self.mesh.uvs[i])),
+            hit,
+            self.mesh.materials[face.material].clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle::bounding_box(self.vertices()))
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
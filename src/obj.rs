@@ -0,0 +1,404 @@
+//! Wavefront OBJ/MTL loader.
+//!
+//! Supports `v`, `vt`, `vn`, `f` (polygons are fan-triangulated, negative
+//! indices allowed), `mtllib` and `usemtl`. Other statements are ignored.
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+    sync::Arc,
+};
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal, Scatter};
+use crate::mesh::{MeshData, MeshFace, TriangleMesh};
+use crate::vec::{Color, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        file: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { file, source } => write!(f, "{}: {}", file.display(), source),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Loads an OBJ file and the `.mtl` libraries it references.
+/// Faces before any `usemtl` get `default_material`.
+pub fn load_obj(path: &Path, default_material: Arc<dyn Scatter>) -> Result<TriangleMesh, ObjError> {
+    let source = read(path)?;
+    let data = parse_obj(&source, path, default_material)?;
+    Ok(TriangleMesh::new(data))
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        file: path.to_path_buf(),
+        source,
+    })
+}
+
+//tracks file and line for error messages
+struct Cursor<'a> {
+    file: &'a Path,
+    line: usize,
+}
+
+impl Cursor<'_> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            file: self.file.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn float(&self, tokens: &mut SplitWhitespace, what: &str) -> Result<f64, ObjError> {
+        let token = tokens
+            .next()
+            .ok_or_else(|| self.error(format!("missing {}", what)))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} '{}'", what, token)))
+    }
+
+    fn vec3(&self, tokens: &mut SplitWhitespace, what: &str) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.float(tokens, what)?,
+            self.float(tokens, what)?,
+            self.float(tokens, what)?,
+        ))
+    }
+
+    //OBJ indices are 1-based, negative ones count back from the end
+    fn index(&self, token: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let i: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index '{}'", what, token)))?;
+        let resolved = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range", what, i)));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+pub fn parse_obj(
+    source: &str,
+    file: &Path,
+    default_material: Arc<dyn Scatter>,
+) -> Result<MeshData, ObjError> {
+    let mut data = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        faces: Vec::new(),
+        materials: vec![default_material],
+    };
+    let mut material_ids: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (n, line) in source.lines().enumerate() {
+        let cur = Cursor { file, line: n + 1 };
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => data
+                .positions
+                .push(cur.vec3(&mut tokens, "vertex coordinate")?),
+            "vn" => data
+                .normals
+                .push(cur.vec3(&mut tokens, "normal coordinate")?),
+            "vt" => {
+                let u = cur.float(&mut tokens, "texture coordinate")?;
+                let v = match tokens.next() {
+                    Some(t) => t
+                        .parse()
+                        .map_err(|_| cur.error(format!("invalid texture coordinate '{}'", t)))?,
+                    None => 0.0,
+                };
+                data.uvs.push((u, v));
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for token in tokens {
+                    corners.push(parse_corner(&cur, token, &data)?);
+                }
+                if corners.len() < 3 {
+                    return Err(cur.error(format!(
+                        "face needs at least 3 vertices, got {}",
+                        corners.len()
+                    )));
+                }
+                let has_uv = corners.iter().all(|c| c.1.is_some());
+                let has_normal = corners.iter().all(|c| c.2.is_some());
+                //fan triangulation
+                for i in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[i], corners[i + 1]];
+                    data.faces.push(MeshFace {
+                        vertices: tri.map(|c| c.0),
+                        uvs: has_uv.then(|| tri.map(|c| c.1.unwrap_or(0))),
+                        normals: has_normal.then(|| tri.map(|c| c.2.unwrap_or(0))),
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" => {
+                let dir = file.parent().unwrap_or(Path::new(""));
+                for name in tokens {
+                    let mtl_path = dir.join(name);
+                    let mtl_source = read(&mtl_path)?;
+                    for (name, spec) in parse_mtl(&mtl_source, &mtl_path)? {
+                        data.materials.push(spec.to_material());
+                        material_ids.insert(name, data.materials.len() - 1);
+                    }
+                }
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| cur.error("missing material name".to_string()))?;
+                current_material = *material_ids
+                    .get(name)
+                    .ok_or_else(|| cur.error(format!("unknown material '{}'", name)))?;
+            }
+            //groups, objects, smoothing groups, lines, ...
+            _ => {}
+        }
+    }
+
+    Ok(data)
+}
+
+// v, v/vt, v//vn or v/vt/vn
+fn parse_corner(
+    cur: &Cursor,
+    token: &str,
+    data: &MeshData,
+) -> Result<(usize, Option<usize>, Option<usize>), ObjError> {
+    let mut parts = token.split('/');
+    let v = cur.index(parts.next().unwrap_or(""), data.positions.len(), "vertex")?;
+    let vt = match parts.next() {
+        Some("") | None => None,
+        Some(t) => Some(cur.index(t, data.uvs.len(), "texture")?),
+    };
+    let vn = match parts.next() {
+        Some("") | None => None,
+        Some(t) => Some(cur.index(t, data.normals.len(), "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(cur.error(format!("invalid face vertex '{}'", token)));
+    }
+    Ok((v, vt, vn))
+}
+
+/// The parameters of one `newmtl` block that map onto our materials.
+pub struct MtlSpec {
+    pub kd: Color,
+    pub ks: Color,
+    pub ke: Color,
+    pub ns: f64,
+    pub ni: f64,
+    pub d: f64,
+}
+
+impl Default for MtlSpec {
+    fn default() -> Self {
+        MtlSpec {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ke: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.0,
+            d: 1.0,
+        }
+    }
+}
+
+impl MtlSpec {
+    /// Emissive -> `DiffuseLight`, transparent -> `Dielectric`,
+    /// specular dominated -> `Metal`, otherwise `Lambertian`.
+    pub fn to_material(&self) -> Arc<dyn Scatter> {
+        let max = |c: Color| c.x().max(c.y()).max(c.z());
+        if max(self.ke) > 0.0 {
+            Arc::new(DiffuseLight::new(self.ke))
+        } else if self.d < 1.0 {
+            let ir = if self.ni > 1.0 { self.ni } else { 1.5 };
+            Arc::new(Dielectric::new(ir))
+        } else if max(self.ks) > max(self.kd) {
+            // Phong exponent to roughness
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            Arc::new(Metal::new(self.ks, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.kd))
+        }
+    }
+}
+
+pub fn parse_mtl(source: &str, file: &Path) -> Result<Vec<(String, MtlSpec)>, ObjError> {
+    let mut materials: Vec<(String, MtlSpec)> = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        let cur = Cursor { file, line: n + 1 };
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| cur.error("missing material name".to_string()))?;
+            materials.push((name.to_string(), MtlSpec::default()));
+            continue;
+        }
+        let is_known = matches!(keyword, "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr");
+        if !is_known {
+            continue;
+        }
+        let Some((_, spec)) = materials.last_mut() else {
+            return Err(cur.error(format!("'{}' before newmtl", keyword)));
+        };
+        match keyword {
+            "Kd" => spec.kd = cur.vec3(&mut tokens, "Kd")?,
+            "Ks" => spec.ks = cur.vec3(&mut tokens, "Ks")?,
+            "Ke" => spec.ke = cur.vec3(&mut tokens, "Ke")?,
+            "Ns" => spec.ns = cur.float(&mut tokens, "Ns")?,
+            "Ni" => spec.ni = cur.float(&mut tokens, "Ni")?,
+            "d" => spec.d = cur.float(&mut tokens, "d")?,
+            "Tr" => spec.d = 1.0 - cur.float(&mut tokens, "Tr")?,
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::Hit;
+    use crate::ray::Ray;
+    use crate::vec::Point3;
+
+    fn default_material() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_quad_is_fan_triangulated() {
+        let source = "\
+# unit quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+";
+        let data = parse_obj(source, Path::new("quad.obj"), default_material()).unwrap();
+        assert_eq!(data.faces.len(), 2);
+        assert_eq!(data.faces[1].vertices, [0, 2, 3]);
+        assert!(data.faces[0].uvs.is_some());
+        assert!(data.faces[0].normals.is_some());
+
+        let mesh = TriangleMesh::new(data);
+        let r = Ray::new(Point3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = mesh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.u - 0.25).abs() < 1e-9);
+        assert!((rec.v - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_error_has_line_number() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 x 0\n";
+        let err = parse_obj(source, Path::new("bad.obj"), default_material())
+            .err()
+            .unwrap();
+        match err {
+            ObjError::Parse { line, .. } => assert_eq!(line, 3),
+            _ => panic!("expected parse error"),
+        }
+        assert!(err.to_string().starts_with("bad.obj:3:"));
+    }
+
+    #[test]
+    fn test_index_out_of_range() {
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        let err = parse_obj(source, Path::new("bad.obj"), default_material())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("vertex index 3 out of range"));
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let source = "usemtl missing\n";
+        let err = parse_obj(source, Path::new("bad.obj"), default_material())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("unknown material 'missing'"));
+    }
+
+    #[test]
+    fn test_mtl() {
+        let source = "\
+newmtl glass
+Ni 1.45
+d 0.2
+newmtl gold
+Kd 0.1 0.1 0.1
+Ks 1.0 0.8 0.3
+Ns 200
+newmtl lamp
+Ke 4 4 3
+";
+        let materials = parse_mtl(source, Path::new("test.mtl")).unwrap();
+        assert_eq!(materials.len(), 3);
+        assert_eq!(materials[0].0, "glass");
+        assert!((materials[0].1.ni - 1.45).abs() < f64::EPSILON);
+        assert!((materials[1].1.ks.y() - 0.8).abs() < f64::EPSILON);
+        assert!((materials[2].1.ke.z() - 3.0).abs() < f64::EPSILON);
+
+        let err = parse_mtl("Kd 1 1 1\n", Path::new("test.mtl"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("before newmtl"));
+    }
+}
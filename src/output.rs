@@ -0,0 +1,399 @@
+use std::{
+    io::{self, Cursor, Write},
+    path::Path,
+};
+
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+
+use super::tonemap::DisplayTransform;
+use super::vec::Color;
+
+/// Linear radiance per pixel (already divided by the sample count),
+/// top row first.
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+    //named single-channel planes, e.g. "Z"; only written to EXR
+    pub extra_channels: Vec<(String, Vec<f32>)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    fn max_value(self) -> u16 {
+        match self {
+            BitDepth::Eight => u8::MAX as u16,
+            BitDepth::Sixteen => u16::MAX,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+#[derive(Clone)]
+pub struct WriterOptions {
+    //PNG and PPM
+    pub bit_depth: BitDepth,
+    pub display: DisplayTransform,
+    pub exr_precision: ExrPrecision,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            bit_depth: BitDepth::Eight,
+            display: DisplayTransform::default(),
+            exr_precision: ExrPrecision::Half,
+        }
+    }
+}
+
+pub trait ImageWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Picks the encoder from the file extension.
+/// .png and .ppm are display-referred, .exr, .hdr and .pfm keep the linear radiance.
+pub fn writer_for_path(path: &Path, options: &WriterOptions) -> io::Result<Box<dyn ImageWriter>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("ppm") => Ok(Box::new(PpmWriter {
+            binary: true,
+            depth: options.bit_depth,
+            display: options.display.clone(),
+        })),
+        Some("png") => Ok(Box::new(PngWriter {
+            depth: options.bit_depth,
+            display: options.display.clone(),
+        })),
+        Some("exr") => Ok(Box::new(ExrWriter {
+            precision: options.exr_precision,
+        })),
+        Some("hdr") => Ok(Box::new(RadianceWriter)),
+        Some("pfm") => Ok(Box::new(PfmWriter)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{}: unsupported image format, expected .png, .ppm, .exr, .hdr or .pfm",
+                path.display()
+            ),
+        )),
+    }
+}
+
+/// Plain-text P3, for writing to stdout.
+pub fn ascii_ppm_writer(options: &WriterOptions) -> Box<dyn ImageWriter> {
+    Box::new(PpmWriter {
+        binary: false,
+        depth: options.bit_depth,
+        display: options.display.clone(),
+    })
+}
+
+/// Scales display values in [0, 1] to 0..=max.
+pub fn quantize(display: Color, depth: BitDepth) -> [u16; 3] {
+    let max = depth.max_value() as f64;
+    let q = |x: f64| ((max + 1.0) * x.clamp(0.0, 1.0)).min(max) as u16;
+    [q(display.x()), q(display.y()), q(display.z())]
+}
+
+//the one place where linear radiance becomes display values
+fn ldr_pixels<'a>(
+    image: &'a Framebuffer,
+    display: &'a DisplayTransform,
+    depth: BitDepth,
+) -> impl Iterator<Item = [u16; 3]> + 'a {
+    image
+        .pixels
+        .iter()
+        .map(move |c| quantize(display.apply(*c), depth))
+}
+
+struct PpmWriter {
+    binary: bool,
+    depth: BitDepth,
+    display: DisplayTransform,
+}
+
+impl ImageWriter for PpmWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+        let magic = if self.binary { "P6" } else { "P3" };
+        writeln!(out, "{}", magic)?;
+        writeln!(out, "{} {}", image.width, image.height)?;
+        writeln!(out, "{}", self.depth.max_value())?;
+
+        for rgb in ldr_pixels(image, &self.display, self.depth) {
+            match (self.binary, self.depth) {
+                (false, _) => writeln!(out, "{} {} {}", rgb[0], rgb[1], rgb[2])?,
+                (true, BitDepth::Eight) => out.write_all(&rgb.map(|c| c as u8))?,
+                // 16-bit samples are big-endian
+                (true, BitDepth::Sixteen) => {
+                    for c in rgb {
+                        out.write_all(&c.to_be_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct PngWriter {
+    depth: BitDepth,
+    display: DisplayTransform,
+}
+
+impl ImageWriter for PngWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        let mut data = Vec::with_capacity(image.pixels.len() * 6);
+        match self.depth {
+            BitDepth::Eight => {
+                encoder.set_depth(png::BitDepth::Eight);
+                for rgb in ldr_pixels(image, &self.display, self.depth) {
+                    data.extend(rgb.map(|c| c as u8));
+                }
+            }
+            BitDepth::Sixteen => {
+                encoder.set_depth(png::BitDepth::Sixteen);
+                for rgb in ldr_pixels(image, &self.display, self.depth) {
+                    for c in rgb {
+                        data.extend(c.to_be_bytes());
+                    }
+                }
+            }
+        }
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+struct ExrWriter {
+    precision: ExrPrecision,
+}
+
+impl ImageWriter for ExrWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+        let samples = |values: Vec<f32>| match self.precision {
+            ExrPrecision::Half => FlatSamples::F16(values.into_iter().map(f16::from_f32).collect()),
+            ExrPrecision::Float => FlatSamples::F32(values),
+        };
+        let plane = |i: usize| image.pixels.iter().map(|c| c[i] as f32).collect();
+
+        let mut channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = SmallVec::new();
+        for (i, name) in ["R", "G", "B"].into_iter().enumerate() {
+            channels.push(AnyChannel::new(name, samples(plane(i))));
+        }
+        for (name, values) in image.extra_channels.iter() {
+            channels.push(AnyChannel::new(name.as_str(), samples(values.clone())));
+        }
+
+        let layer = Layer::new(
+            (image.width, image.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+        // the encoder needs to seek, so build the file in memory first
+        let mut buffer = Cursor::new(Vec::new());
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(&mut buffer)
+            .map_err(io::Error::other)?;
+        out.write_all(buffer.get_ref())
+    }
+}
+
+// Radiance RGBE: shared 8-bit exponent, scanlines stored flat (no RLE)
+struct RadianceWriter;
+
+fn rgbe(color: Color) -> [u8; 4] {
+    let v = color.x().max(color.y()).max(color.z());
+    if v < 1.0e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    let c = |x: f64| (x.max(0.0) * scale).min(255.0) as u8;
+    [c(color.x()), c(color.y()), c(color.z()), (e + 128) as u8]
+}
+
+impl ImageWriter for RadianceWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "#?RADIANCE")?;
+        writeln!(out, "FORMAT=32-bit_rle_rgbe")?;
+        writeln!(out)?;
+        writeln!(out, "-Y {} +X {}", image.height, image.width)?;
+        for color in image.pixels.iter() {
+            out.write_all(&rgbe(*color))?;
+        }
+        Ok(())
+    }
+}
+
+// Portable float map, little-endian, bottom row first
+struct PfmWriter;
+
+impl ImageWriter for PfmWriter {
+    fn write(&self, image: &Framebuffer, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "PF")?;
+        writeln!(out, "{} {}", image.width, image.height)?;
+        writeln!(out, "-1.0")?;
+        for row in image.pixels.chunks(image.width).rev() {
+            for color in row {
+                for i in 0..3 {
+                    out.write_all(&(color[i] as f32).to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny() -> Framebuffer {
+        Framebuffer {
+            width: 2,
+            height: 1,
+            pixels: vec![Color::new(0.0, 0.25, 4.0), Color::new(1.0, 1.0, 1.0)],
+            extra_channels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(
+            quantize(Color::new(0.0, 0.5, 4.0), BitDepth::Eight),
+            [0, 128, 255]
+        );
+        assert_eq!(
+            quantize(Color::new(-1.0, 1.0, 1.0), BitDepth::Sixteen),
+            [0, 65535, 65535]
+        );
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut out = Vec::new();
+        ascii_ppm_writer(&WriterOptions::default())
+            .write(&tiny(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P3\n2 1\n255\n0 137 255\n255 255 255\n"
+        );
+
+        let mut out = Vec::new();
+        writer_for_path(Path::new("a.PPM"), &WriterOptions::default())
+            .unwrap()
+            .write(&tiny(), &mut out)
+            .unwrap();
+        assert_eq!(out[..11], *b"P6\n2 1\n255\n");
+        assert_eq!(out[11..], [0, 137, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_png_signature() {
+        for depth in [BitDepth::Eight, BitDepth::Sixteen] {
+            let mut out = Vec::new();
+            let options = WriterOptions {
+                bit_depth: depth,
+                ..Default::default()
+            };
+            writer_for_path(Path::new("a.png"), &options)
+                .unwrap()
+                .write(&tiny(), &mut out)
+                .unwrap();
+            assert_eq!(out[..8], [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]);
+        }
+    }
+
+    #[test]
+    fn test_unknown_extension() {
+        assert!(writer_for_path(Path::new("a.bmp"), &WriterOptions::default()).is_err());
+        assert!(writer_for_path(Path::new("noext"), &WriterOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(rgbe(Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        // 1.0 = 0.5 * 2^1
+        assert_eq!(rgbe(Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        // values above 1 survive
+        assert_eq!(rgbe(Color::new(6.0, 0.0, 0.0)), [192, 0, 0, 131]);
+    }
+
+    #[test]
+    fn test_pfm_is_bottom_up() {
+        let image = Framebuffer {
+            width: 1,
+            height: 2,
+            pixels: vec![Color::new(1.0, 1.0, 1.0), Color::new(2.0, 2.0, 2.0)],
+            extra_channels: Vec::new(),
+        };
+        let mut out = Vec::new();
+        writer_for_path(Path::new("a.pfm"), &WriterOptions::default())
+            .unwrap()
+            .write(&image, &mut out)
+            .unwrap();
+        assert_eq!(out[..12], *b"PF\n1 2\n-1.0\n");
+        assert_eq!(out[12..16], 2.0f32.to_le_bytes());
+        assert_eq!(out[24..28], 1.0f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_exr_with_extra_channels() {
+        use exr::prelude::{ReadChannels, ReadLayers};
+
+        for precision in [ExrPrecision::Half, ExrPrecision::Float] {
+            let mut image = tiny();
+            image.extra_channels.push(("Z".to_string(), vec![1.0, 2.0]));
+            let options = WriterOptions {
+                exr_precision: precision,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            writer_for_path(Path::new("a.exr"), &options)
+                .unwrap()
+                .write(&image, &mut out)
+                .unwrap();
+            assert_eq!(out[..4], [0x76, 0x2f, 0x31, 0x01]);
+
+            let read = exr::prelude::read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+                .from_buffered(Cursor::new(out))
+                .unwrap();
+            let channels = &read.layer_data.channel_data.list;
+            let names: Vec<String> = channels.iter().map(|c| c.name.to_string()).collect();
+            assert_eq!(names, ["B", "G", "R", "Z"]);
+            // linear values above 1 are kept
+            assert_eq!(channels[0].sample_data.value_by_flat_index(0).to_f32(), 4.0);
+            assert_eq!(channels[3].sample_data.value_by_flat_index(1).to_f32(), 2.0);
+        }
+    }
+}
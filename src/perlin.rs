@@ -0,0 +1,132 @@
+//! Perlin gradient noise, seeded so a texture looks the same every render.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::vec::{Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ranvec = (0..POINT_COUNT)
+            .map(|_| {
+                //random unit gradient
+                loop {
+                    let v = Vec3::new(
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                    );
+                    let len = v.length();
+                    if len > 1e-3 && len <= 1.0 {
+                        return v / len;
+                    }
+                }
+            })
+            .collect();
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let perm_x = perm();
+        let perm_y = perm();
+        let perm_z = perm();
+        Perlin {
+            ranvec,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// In about [-1, 1], zero at integer lattice points.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let (i, j, k) = (
+            p.x().floor() as i64,
+            p.y().floor() as i64,
+            p.z().floor() as i64,
+        );
+        let (u, v, w) = (p.x() - i as f64, p.y() - j as f64, p.z() - k as f64);
+        //Hermite smoothing
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+
+        let mask = (POINT_COUNT - 1) as i64;
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.ranvec[self.perm_x[((i + di) & mask) as usize]
+                        ^ self.perm_y[((j + dj) & mask) as usize]
+                        ^ self.perm_z[((k + dk) & mask) as usize]];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Signed sum of `octaves` layers, each twice the frequency and half the
+    /// amplitude of the one before.
+    pub fn fbm(&self, p: &Point3, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = 2.0 * temp_p;
+        }
+        accum
+    }
+
+    /// Absolute value of `fbm`, creased where it crosses zero.
+    pub fn turb(&self, p: &Point3, depth: u32) -> f64 {
+        self.fbm(p, depth).abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded() {
+        let p = Point3::new(1.3, -2.7, 0.45);
+        assert_eq!(Perlin::new(7).noise(&p), Perlin::new(7).noise(&p));
+        assert_ne!(Perlin::new(7).noise(&p), Perlin::new(8).noise(&p));
+    }
+
+    #[test]
+    fn test_noise_range() {
+        let perlin = Perlin::new(1);
+        let mut any_nonzero = false;
+        for i in 0..1000 {
+            let x = i as f64 * 0.137;
+            let n = perlin.noise(&Point3::new(x, x * 0.7 - 3.0, 1.0 - x * 0.3));
+            assert!(n.abs() <= 1.0);
+            any_nonzero |= n.abs() > 1e-3;
+        }
+        assert!(any_nonzero);
+        // zero on the lattice
+        assert!(perlin.noise(&Point3::new(3.0, -4.0, 5.0)).abs() < 1e-12);
+        assert!(perlin.turb(&Point3::new(0.3, 0.2, 0.1), 7) >= 0.0);
+    }
+}
@@ -0,0 +1,253 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::light::Light;
+use super::random::random_double;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+//t of the ray's crossing of the plane through `point`, if in (t_min, t_max)
+fn hit_plane(r: &Ray, point: Point3, normal: Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denom = normal.dot(r.direction());
+    //parallel to the plane
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+    let t = normal.dot(point - r.origin()) / denom;
+    if t <= t_min || t >= t_max {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+/// Infinite plane through `point`, facing along `normal`. It has no
+/// bounding box, so a BVH keeps it out of the tree.
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    //in-plane axes for (u, v)
+    tangent: Vec3,
+    bitangent: Vec3,
+    tile_size: Option<f64>,
+    material: Arc<dyn Scatter>,
+}
+
+impl Plane {
+    /// (u, v) are distances from `point` along two axes in the plane.
+    pub fn new(point: Point3, normal: Vec3, m: Arc<dyn Scatter>) -> Plane {
+        let normal = normal.normalized();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            tile_size: None,
+            material: m,
+        }
+    }
+
+    //(u, v) repeat in [0, 1) every `size` units, for image textures
+    pub fn tiled(mut self, size: f64) -> Plane {
+        self.tile_size = Some(size);
+        self
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(r, self.point, self.normal, t_min, t_max)?;
+        let p = r.at(t);
+        let offset = p - self.point;
+        let (mut u, mut v) = (offset.dot(self.tangent), offset.dot(self.bitangent));
+        if let Some(size) = self.tile_size {
+            u = (u / size).rem_euclid(1.0);
+            v = (v / size).rem_euclid(1.0);
+        }
+        let mut rec = HitRecord {
+            p,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t,
+            u,
+            v,
+            barycentric: None,
+            front_face: false,
+            material: self.material.clone(),
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Flat disk around `center` facing along `normal`, with a hole of
+/// `inner_radius` for an annulus. u goes around, v from the inner edge out.
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    radius: f64,
+    inner_radius: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, m: Arc<dyn Scatter>) -> Disk {
+        let normal = normal.normalized();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Disk {
+            center,
+            normal,
+            tangent,
+            bitangent,
+            radius,
+            inner_radius: 0.0,
+            material: m,
+        }
+    }
+
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Disk {
+        self.inner_radius = inner_radius;
+        self
+    }
+
+    fn area(&self) -> f64 {
+        PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(r, self.center, self.normal, t_min, t_max)?;
+        let p = r.at(t);
+        let offset = p - self.center;
+        let distance = offset.length();
+        if distance > self.radius || distance < self.inner_radius {
+            return None;
+        }
+        let phi = offset.dot(self.bitangent).atan2(offset.dot(self.tangent));
+        let mut rec = HitRecord {
+            p,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t,
+            u: (phi / (2.0 * PI)).rem_euclid(1.0),
+            v: (distance - self.inner_radius) / (self.radius - self.inner_radius),
+            barycentric: None,
+            front_face: false,
+            material: self.material.clone(),
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        //extent of a circle along each axis is r * sqrt(1 - n_axis^2), padded
+        //so a disk facing along an axis doesn't give a flat box
+        const PAD: f64 = 1.0e-4;
+        let n = self.normal;
+        let extent = |a: f64| self.radius * (1.0 - a * a).max(0.0).sqrt() + PAD;
+        let e = Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+        Some(Aabb::new(self.center - e, self.center + e))
+    }
+}
+
+//uniform over the area, converted to solid angle at `origin`
+impl Light for Disk {
+    fn sample_light(&self, origin: Point3) -> Option<(Vec3, f64)> {
+        let (r0, r1) = (self.inner_radius, self.radius);
+        let radius = (r0 * r0 + random_double() * (r1 * r1 - r0 * r0)).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let point = self.center + radius * (phi.cos() * self.tangent + phi.sin() * self.bitangent);
+        let direction = (point - origin).normalized();
+        let pdf = self.pdf(origin, direction);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+
+    fn pdf(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let distance = rec.t * direction.length();
+        let cosine = (direction.dot(self.normal) / direction.length()).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance * distance / (cosine * self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::hit::World;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::Color;
+
+    fn grey() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_plane_in_bvh() {
+        let ground = Plane::new(
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            grey(),
+        );
+        let world: World = vec![
+            Arc::new(ground.tiled(0.5)),
+            Arc::new(Sphere::new(Point3::new(5.0, 0.0, -5.0), 1.0, grey())),
+        ];
+        let bvh = BvhNode::new(world);
+        assert!(bvh.bounding_box().is_none());
+
+        // far away along the ground, well outside the sphere's box
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -0.01, -1.0), 0.0);
+        let rec = bvh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 100.0).abs() < 1e-9);
+        assert!(rec.front_face && (rec.normal.y() - 1.0).abs() < 1e-12);
+        assert!((0.0..1.0).contains(&rec.u) && (0.0..1.0).contains(&rec.v));
+        // the sphere in front still wins
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, -0.1, -1.0), 0.0);
+        let rec = bvh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!(rec.p.y() > -1.0 && rec.t < 5.0);
+    }
+
+    #[test]
+    fn test_annulus() {
+        let disk = Disk::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            2.0,
+            grey(),
+        )
+        .with_inner_radius(1.0);
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let at = |x: f64| disk.hit(&Ray::new(Point3::new(x, 0.0, 1.0), down, 0.0), 0.001, 10.0);
+        assert!(at(0.5).is_none());
+        assert!(at(2.5).is_none());
+        assert!((at(1.5).unwrap().v - 0.5).abs() < 1e-12);
+
+        let origin = Point3::new(0.3, -0.2, 1.5);
+        for _ in 0..100 {
+            let (direction, pdf) = disk.sample_light(origin).unwrap();
+            assert!((pdf - disk.pdf(origin, direction)).abs() < 1e-9 * pdf);
+        }
+        let bbox = disk.bounding_box().unwrap();
+        assert!((bbox.max().x() - 2.0).abs() < 1e-3 && bbox.max().z() < 1e-3);
+    }
+}
@@ -0,0 +1,242 @@
+//! Real roots of low-degree polynomials for analytic shapes.
+//!
+//! Closed forms lose digits when roots cluster or coefficients differ in
+//! scale, so cubic and quartic roots are polished with Newton steps on the
+//! original polynomial.
+
+use std::f64::consts::PI;
+
+/// Real roots of a t^2 + b t + c, smallest first; a may be zero.
+/// Avoids the cancellation of the textbook formula.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    //as a shrinks, c/q below tends to the root of the line and q/a runs
+    //off to infinity, so only a = 0 itself needs a case and tiny or
+    //short-direction coefficients need no threshold
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let t0 = q / a;
+    let t1 = if q == 0.0 { t0 } else { c / q };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Value and derivative at `x` of the polynomial with `coeffs`, highest
+/// degree first.
+pub fn eval(coeffs: &[f64], x: f64) -> (f64, f64) {
+    coeffs
+        .iter()
+        .fold((0.0, 0.0), |(p, dp), &c| (p * x + c, dp * x + p))
+}
+
+/// Newton steps from `x` towards a root of `coeffs`, keeping the best
+/// estimate seen in case they wander off near a multiple root.
+pub fn polish(coeffs: &[f64], x: f64) -> f64 {
+    let (mut best, mut best_error) = (x, eval(coeffs, x).0.abs());
+    let mut x = x;
+    for _ in 0..8 {
+        let (p, dp) = eval(coeffs, x);
+        if p == 0.0 || dp == 0.0 {
+            break;
+        }
+        x -= p / dp;
+        let error = eval(coeffs, x).0.abs();
+        if error < best_error {
+            best = x;
+            best_error = error;
+        } else if error > best_error {
+            break;
+        }
+    }
+    best
+}
+
+/// Real roots of x^3 + a x^2 + b x + c in increasing order, a repeated
+/// root possibly more than once.
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    //depressed t^3 + p t + q with x = t - a/3
+    let shift = a / 3.0;
+    let p = b - a * shift;
+    let q = c - b * shift + 2.0 * shift * shift * shift;
+    let half_q = q / 2.0;
+    let third_p = p / 3.0;
+    let discriminant = half_q * half_q + third_p * third_p * third_p;
+
+    let mut roots = if discriminant > 0.0 {
+        //one real root, Cardano
+        let s = discriminant.sqrt();
+        vec![(-half_q + s).cbrt() + (-half_q - s).cbrt()]
+    } else if third_p == 0.0 {
+        vec![0.0]
+    } else {
+        //three real roots, trigonometric
+        let m = 2.0 * (-third_p).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| m * (theta - 2.0 * PI * k as f64 / 3.0).cos())
+            .collect()
+    };
+    let coeffs = [1.0, a, b, c];
+    for x in roots.iter_mut() {
+        *x = polish(&coeffs, *x - shift);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Real roots of c4 x^4 + c3 x^3 + c2 x^2 + c1 x + c0 in increasing order.
+/// Falls back to the cubic when c4 is negligible.
+pub fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    //c4 only adds a root near -c3/c4; it is negligible when that root lies
+    //far beyond Fujiwara's bound on the cubic's roots, which holds at any
+    //scale of the coefficients or of x
+    let bound = 2.0
+        * (c2 / c3)
+            .abs()
+            .max((c1 / c3).abs().sqrt())
+            .max((c0 / c3).abs().cbrt());
+    if c4 == 0.0 || (bound > 0.0 && c4.abs() * bound <= 1e-12 * c3.abs()) {
+        if c3 == 0.0 {
+            return solve_quadratic(c2, c1, c0)
+                .map(|(t0, t1)| if t0 == t1 { vec![t0] } else { vec![t0, t1] })
+                .unwrap_or_default();
+        }
+        return solve_cubic(c2 / c3, c1 / c3, c0 / c3);
+    }
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+    //depressed y^4 + p y^2 + q y + r with x = y - a/4
+    let shift = a / 4.0;
+    let shift2 = shift * shift;
+    let p = b - 6.0 * shift2;
+    let q = c - 2.0 * b * shift + 8.0 * shift2 * shift;
+    let r = d - c * shift + b * shift2 - 3.0 * shift2 * shift2;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut push_quadratic = |b: f64, c: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, b, c) {
+            roots.push(y0);
+            roots.push(y1);
+        }
+    };
+    if q.abs() < 1e-12 * (1.0 + p.abs() + r.abs()) {
+        //biquadratic in y^2
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    push_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        //factor into (y^2 + s y + u)(y^2 - s y + v) with z = s^2 the
+        //largest root of the resolvent cubic, which is positive since q != 0
+        let z = solve_cubic(2.0 * p, p * p - 4.0 * r, -q * q)
+            .last()
+            .copied()
+            .unwrap_or(0.0);
+        if z > 0.0 {
+            let s = z.sqrt();
+            let half = (p + z) / 2.0;
+            let skew = q / (2.0 * s);
+            push_quadratic(s, half - skew);
+            push_quadratic(-s, half + skew);
+        }
+    }
+    let coeffs = [1.0, a, b, c, d];
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|y| polish(&coeffs, y - shift))
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(found: &[f64], expected: &[f64], eps: f64) {
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (f, e) in found.iter().zip(expected) {
+            assert!((f - e).abs() < eps, "{:?} vs {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn test_solve_quadratic() {
+        let (t0, t1) = solve_quadratic(1.0, -3.0, 2.0).unwrap();
+        assert!((t0 - 1.0).abs() < 1e-12 && (t1 - 2.0).abs() < 1e-12);
+        // tiny root next to a huge one keeps its precision
+        let (t0, _) = solve_quadratic(1.0, 1.0e8, 1.0).unwrap();
+        assert!((t0 + 1.0e8).abs() < 1.0);
+        let (_, t1) = solve_quadratic(1.0, 1.0e8, 1.0).unwrap();
+        assert!((t1 + 1.0e-8).abs() < 1e-20);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert!(solve_quadratic(0.0, 0.0, 0.0).is_none());
+        // tiny coefficients are still a quadratic, not a line
+        let (t0, t1) = solve_quadratic(1.0e-14, -3.0e-14, 2.0e-14).unwrap();
+        assert!((t0 - 1.0).abs() < 1e-12 && (t1 - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_solve_cubic() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(&solve_cubic(-6.0, 11.0, -6.0), &[1.0, 2.0, 3.0], 1e-12);
+        // (x - 2)(x^2 + 1)
+        assert_roots(&solve_cubic(-2.0, 1.0, -2.0), &[2.0], 1e-12);
+        assert_roots(&solve_cubic(0.0, 0.0, -8.0), &[2.0], 1e-12);
+        assert_roots(&solve_cubic(0.0, 0.0, 0.0), &[0.0], 1e-12);
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4), scaled
+        let roots = solve_quartic(2.0, -20.0, 70.0, -100.0, 48.0);
+        assert_roots(&roots, &[1.0, 2.0, 3.0, 4.0], 1e-10);
+        // biquadratic (x^2 - 1)(x^2 - 4)
+        assert_roots(
+            &solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+            1e-12,
+        );
+        // (x^2 + 1)(x^2 + 2) has none
+        assert!(solve_quartic(1.0, 0.0, 3.0, 0.0, 2.0).is_empty());
+        // (x - 0.5)(x + 3)(x^2 + x + 1): two real, two complex
+        let roots = solve_quartic(1.0, 3.5, 2.0, 1.0, -1.5);
+        assert_roots(&roots, &[-3.0, 0.5], 1e-10);
+        // clustered far from the origin, as for a ray starting far from a
+        // torus: only as good as the ~1e12 coefficients allow
+        let (r0, r1, r2, r3) = (1000.0, 1000.5, 1003.0, 1004.0);
+        let roots = solve_quartic(
+            1.0,
+            -(r0 + r1 + r2 + r3),
+            r0 * r1 + r0 * r2 + r0 * r3 + r1 * r2 + r1 * r3 + r2 * r3,
+            -(r0 * r1 * r2 + r0 * r1 * r3 + r0 * r2 * r3 + r1 * r2 * r3),
+            r0 * r1 * r2 * r3,
+        );
+        assert_roots(&roots, &[r0, r1, r2, r3], 1e-3);
+        // the same quartic at a tiny scale
+        let roots = solve_quartic(2.0e-14, -20.0e-14, 70.0e-14, -100.0e-14, 48.0e-14);
+        assert_roots(&roots, &[1.0, 2.0, 3.0, 4.0], 1e-10);
+        // degree drops to a cubic
+        assert_roots(
+            &solve_quartic(1.0e-20, 1.0, -6.0, 11.0, -6.0),
+            &[1.0, 2.0, 3.0],
+            1e-12,
+        );
+        assert_roots(
+            &solve_quartic(0.0, 1.0, -6.0, 11.0, -6.0),
+            &[1.0, 2.0, 3.0],
+            1e-12,
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::light::Light;
+use super::random::random_double;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Parallelogram with corner `q` and edges `u` and `v`, facing along u × v.
+/// (u, v) of a hit are the fractions along the two edges.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    //unit normal, and the plane n·p = d
+    normal: Vec3,
+    d: f64,
+    //n / (n·n) for the unnormalized n = u × v, projects onto the edges
+    w: Vec3,
+    area: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, m: Arc<dyn Scatter>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.normalized();
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.length(),
+            material: m,
+        }
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction());
+        //parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        //position in edge coordinates
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord {
+            p,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t,
+            u: alpha,
+            v: beta,
+            barycentric: None,
+            front_face: false,
+            material: self.material.clone(),
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        //pad so axis-aligned quads don't give a flat box
+        const PAD: f64 = 1.0e-4;
+        let diagonal = Aabb::new(self.q, self.q + self.u + self.v);
+        let other = Aabb::new(self.q + self.u, self.q + self.v);
+        let bbox = Aabb::surrounding(diagonal, other);
+        let mut min = bbox.min();
+        let mut max = bbox.max();
+        for a in 0..3 {
+            if max[a] - min[a] < PAD {
+                min[a] -= PAD / 2.0;
+                max[a] += PAD / 2.0;
+            }
+        }
+        Some(Aabb::new(min, max))
+    }
+}
+
+//uniform over the area, converted to solid angle at `origin`
+impl Light for Quad {
+    fn sample_light(&self, origin: Point3) -> Option<(Vec3, f64)> {
+        let point = self.q + random_double() * self.u + random_double() * self.v;
+        let direction = (point - origin).normalized();
+        let pdf = self.pdf(origin, direction);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+
+    fn pdf(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let distance = rec.t * direction.length();
+        let cosine = (direction.dot(self.normal) / direction.length()).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance * distance / (cosine * self.area)
+    }
+}
+
+/// Axis-aligned box between two opposite corners, six quads facing out.
+pub struct BoxShape {
+    sides: Vec<Arc<Quad>>,
+    bbox: Aabb,
+}
+
+impl BoxShape {
+    pub fn new(a: Point3, b: Point3, m: Arc<dyn Scatter>) -> BoxShape {
+        let bbox = Aabb::new(a, b);
+        let (min, max) = (bbox.min(), bbox.max());
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+        let side = |q: Point3, u: Vec3, v: Vec3| Arc::new(Quad::new(q, u, v, m.clone()));
+        let sides = vec![
+            //front, right, back, left, top, bottom
+            side(Point3::new(min.x(), min.y(), max.z()), dx, dy),
+            side(Point3::new(max.x(), min.y(), max.z()), (-1.0) * dz, dy),
+            side(Point3::new(max.x(), min.y(), min.z()), (-1.0) * dx, dy),
+            side(min, dz, dy),
+            side(Point3::new(min.x(), max.y(), max.z()), dx, (-1.0) * dz),
+            side(min, dx, dz),
+        ];
+        BoxShape { sides, bbox }
+    }
+
+    /// The six faces, e.g. to sample a glowing box as lights.
+    pub fn sides(&self) -> &[Arc<Quad>] {
+        &self.sides
+    }
+}
+
+impl Hit for BoxShape {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        for side in self.sides.iter() {
+            if let Some(rec) = side.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vec::Color;
+
+    fn unit_quad() -> Quad {
+        // 2 x 1 in the z = -1 plane, facing +z
+        Quad::new(
+            Point3::new(-1.0, 0.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_hit_uv() {
+        let quad = unit_quad();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let r = Ray::new(origin, Vec3::new(0.5, 0.25, -1.0), 0.0);
+        let rec = quad.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.75).abs() < 1e-12 && (rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face && (rec.normal.z() - 1.0).abs() < 1e-12);
+        assert!(quad
+            .hit(
+                &Ray::new(origin, Vec3::new(1.5, 0.5, -1.0), 0.0),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        let bbox = quad.bounding_box().unwrap();
+        assert!(bbox.max().z() > bbox.min().z());
+    }
+
+    #[test]
+    fn test_box() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let b = BoxShape::new(
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(-1.0, 0.0, 1.0),
+            material,
+        );
+        let center = Point3::new(0.0, 1.0, 2.0);
+        // every face has its normal pointing out
+        for side in b.sides() {
+            let rec = side
+                .hit(&Ray::new(center, side.normal, 0.0), 0.001, f64::INFINITY)
+                .unwrap();
+            assert!(!rec.front_face);
+        }
+        let r = Ray::new(Point3::new(0.0, 1.0, 10.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = b.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 7.0).abs() < 1e-12);
+        assert!(rec.front_face && (rec.normal.z() - 1.0).abs() < 1e-12);
+        let bbox = b.bounding_box().unwrap();
+        assert!((bbox.min().x() + 1.0).abs() < 1e-12 && (bbox.max().z() - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_light_pdf_integrates_to_one() {
+        // sum pdf over the area: pdf * cos / dist^2 dA = 1
+        let quad = unit_quad();
+        let origin = Point3::new(0.3, 0.2, 0.5);
+        let n = 100;
+        let mut total = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let p = Point3::new(
+                    -1.0 + 2.0 * (i as f64 + 0.5) / n as f64,
+                    (j as f64 + 0.5) / n as f64,
+                    -1.0,
+                );
+                let d = p - origin;
+                let cosine = d.z().abs() / d.length();
+                let da = 2.0 / (n * n) as f64;
+                total += quad.pdf(origin, d) * cosine / d.length().powi(2) * da;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-9);
+
+        for _ in 0..100 {
+            let (direction, pdf) = quad.sample_light(origin).unwrap();
+            assert!((pdf - quad.pdf(origin, direction)).abs() < 1e-9 * pdf);
+        }
+        assert_eq!(quad.pdf(origin, Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+}
@@ -0,0 +1,557 @@
+//! Quadric surfaces standing on a base point with their axis along +y.
+//! `phi_max` sweeps them partially around the axis, starting at +x and
+//! turning towards +z. u goes around the axis, v up it.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::poly::solve_quadratic;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+//hit in the shape's frame, before it becomes a HitRecord
+struct LocalHit {
+    t: f64,
+    normal: Vec3,
+    u: f64,
+    v: f64,
+}
+
+//angle around +y in [0, 2pi), from +x towards +z
+fn phi_of(p: Vec3) -> f64 {
+    p.z().atan2(p.x()).rem_euclid(2.0 * PI)
+}
+
+//nearest root in (t_min, t_max) whose point lies on the surface patch
+fn nearest_root(
+    roots: Option<(f64, f64)>,
+    t_min: f64,
+    t_max: f64,
+    on_patch: impl Fn(f64) -> bool,
+) -> Option<f64> {
+    let (t0, t1) = roots?;
+    [t0, t1]
+        .into_iter()
+        .find(|&t| t > t_min && t < t_max && on_patch(t))
+}
+
+//flat cap at height y of the ray in the shape's frame, a disk sector of `radius`
+fn hit_cap(
+    local: &Ray,
+    y: f64,
+    radius: f64,
+    phi_max: f64,
+    up: bool,
+    t_min: f64,
+    t_max: f64,
+) -> Option<LocalHit> {
+    let (o, d) = (local.origin(), local.direction());
+    //parallel to the cap, relative to the length of d
+    if d.y().abs() <= 1e-12 * d.length() {
+        return None;
+    }
+    let t = (y - o.y()) / d.y();
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    let p = o + t * d;
+    let distance = (p.x() * p.x() + p.z() * p.z()).sqrt();
+    let phi = phi_of(p);
+    if distance > radius || phi > phi_max {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        normal: Vec3::new(0.0, if up { 1.0 } else { -1.0 }, 0.0),
+        u: phi / phi_max,
+        v: distance / radius,
+    })
+}
+
+fn closest(hits: [Option<LocalHit>; 3]) -> Option<LocalHit> {
+    hits.into_iter()
+        .flatten()
+        .min_by(|a, b| a.t.total_cmp(&b.t))
+}
+
+fn record(r: &Ray, hit: LocalHit, material: &Arc<dyn Scatter>) -> HitRecord {
+    let mut rec = HitRecord {
+        p: r.at(hit.t),
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        t: hit.t,
+        u: hit.u,
+        v: hit.v,
+        barycentric: None,
+        front_face: false,
+        material: material.clone(),
+    };
+    rec.set_face_normal(r, hit.normal.normalized());
+    rec
+}
+
+fn check_phi_max(phi_max: f64) -> f64 {
+    phi_max.clamp(0.0, 2.0 * PI)
+}
+
+/// Cylinder of `radius` from `base` up to `height`, optionally closed by
+/// flat caps.
+pub struct Cylinder {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material: Arc<dyn Scatter>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3,
+        radius: f64,
+        height: f64,
+        phi_max: f64,
+        capped: bool,
+        m: Arc<dyn Scatter>,
+    ) -> Cylinder {
+        Cylinder {
+            base,
+            radius,
+            height,
+            phi_max: check_phi_max(phi_max),
+            capped,
+            material: m,
+        }
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin() - self.base, r.direction());
+        let local = Ray::new(o, d, r.time());
+        let roots = solve_quadratic(
+            d.x() * d.x() + d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.z() * d.z()),
+            o.x() * o.x() + o.z() * o.z() - self.radius * self.radius,
+        );
+        let side = nearest_root(roots, t_min, t_max, |t| {
+            let p = o + t * d;
+            (0.0..=self.height).contains(&p.y()) && phi_of(p) <= self.phi_max
+        })
+        .map(|t| {
+            let p = o + t * d;
+            LocalHit {
+                t,
+                normal: Vec3::new(p.x(), 0.0, p.z()),
+                u: phi_of(p) / self.phi_max,
+                v: p.y() / self.height,
+            }
+        });
+        let cap = |y, up| {
+            if self.capped {
+                hit_cap(&local, y, self.radius, self.phi_max, up, t_min, t_max)
+            } else {
+                None
+            }
+        };
+        let hit = closest([side, cap(0.0, false), cap(self.height, true)])?;
+        Some(record(r, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(
+            self.base + Vec3::new(-r, 0.0, -r),
+            self.base + Vec3::new(r, self.height, r),
+        ))
+    }
+}
+
+/// Cone with a base of `radius` and its apex `height` above `base`,
+/// optionally closed at the bottom.
+pub struct Cone {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material: Arc<dyn Scatter>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point3,
+        radius: f64,
+        height: f64,
+        phi_max: f64,
+        capped: bool,
+        m: Arc<dyn Scatter>,
+    ) -> Cone {
+        Cone {
+            base,
+            radius,
+            height,
+            phi_max: check_phi_max(phi_max),
+            capped,
+            material: m,
+        }
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        //x^2 + z^2 = k (h - y)^2
+        let (o, d) = (r.origin() - self.base, r.direction());
+        let local = Ray::new(o, d, r.time());
+        let k = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        let roots = solve_quadratic(
+            d.x() * d.x() + d.z() * d.z() - k * d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.z() * d.z() + k * h * d.y()),
+            o.x() * o.x() + o.z() * o.z() - k * h * h,
+        );
+        let side = nearest_root(roots, t_min, t_max, |t| {
+            let p = o + t * d;
+            (0.0..=self.height).contains(&p.y()) && phi_of(p) <= self.phi_max
+        })
+        .map(|t| {
+            let p = o + t * d;
+            LocalHit {
+                t,
+                normal: Vec3::new(p.x(), k * (self.height - p.y()), p.z()),
+                u: phi_of(p) / self.phi_max,
+                v: p.y() / self.height,
+            }
+        });
+        let bottom = if self.capped {
+            hit_cap(&local, 0.0, self.radius, self.phi_max, false, t_min, t_max)
+        } else {
+            None
+        };
+        let hit = closest([side, bottom, None])?;
+        Some(record(r, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(
+            self.base + Vec3::new(-r, 0.0, -r),
+            self.base + Vec3::new(r, self.height, r),
+        ))
+    }
+}
+
+/// Axis-aligned ellipsoid around `center` with semi-axes `radii`.
+/// v runs from the bottom pole to the top one.
+pub struct Ellipsoid {
+    center: Point3,
+    radii: Vec3,
+    phi_max: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl Ellipsoid {
+    pub fn new(center: Point3, radii: Vec3, phi_max: f64, m: Arc<dyn Scatter>) -> Ellipsoid {
+        Ellipsoid {
+            center,
+            radii,
+            phi_max: check_phi_max(phi_max),
+            material: m,
+        }
+    }
+}
+
+impl Hit for Ellipsoid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        //squashed into the unit sphere
+        let o = r.origin() - self.center;
+        let d = r.direction();
+        let scale = |v: Vec3| {
+            Vec3::new(
+                v.x() / self.radii.x(),
+                v.y() / self.radii.y(),
+                v.z() / self.radii.z(),
+            )
+        };
+        let (os, ds) = (scale(o), scale(d));
+        let roots = solve_quadratic(ds.dot(ds), 2.0 * os.dot(ds), os.dot(os) - 1.0);
+        let t = nearest_root(roots, t_min, t_max, |t| phi_of(o + t * d) <= self.phi_max)?;
+        let p = o + t * d;
+        let radii2 = Vec3::new(
+            self.radii.x().powi(2),
+            self.radii.y().powi(2),
+            self.radii.z().powi(2),
+        );
+        let hit = LocalHit {
+            t,
+            normal: Vec3::new(p.x() / radii2.x(), p.y() / radii2.y(), p.z() / radii2.z()),
+            u: phi_of(p) / self.phi_max,
+            v: (-p.y() / self.radii.y()).clamp(-1.0, 1.0).acos() / PI,
+        };
+        Some(record(r, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(
+            self.radii.x().abs(),
+            self.radii.y().abs(),
+            self.radii.z().abs(),
+        );
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+/// Bowl y = height (x^2 + z^2) / radius^2 with its vertex at `base`,
+/// `radius` wide at the rim, optionally closed by a lid there.
+pub struct Paraboloid {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material: Arc<dyn Scatter>,
+}
+
+impl Paraboloid {
+    pub fn new(
+        base: Point3,
+        radius: f64,
+        height: f64,
+        phi_max: f64,
+        capped: bool,
+        m: Arc<dyn Scatter>,
+    ) -> Paraboloid {
+        Paraboloid {
+            base,
+            radius,
+            height,
+            phi_max: check_phi_max(phi_max),
+            capped,
+            material: m,
+        }
+    }
+}
+
+impl Hit for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        //h (x^2 + z^2) - r^2 y = 0
+        let (o, d) = (r.origin() - self.base, r.direction());
+        let local = Ray::new(o, d, r.time());
+        let (h, r2) = (self.height, self.radius * self.radius);
+        let roots = solve_quadratic(
+            h * (d.x() * d.x() + d.z() * d.z()),
+            2.0 * h * (o.x() * d.x() + o.z() * d.z()) - r2 * d.y(),
+            h * (o.x() * o.x() + o.z() * o.z()) - r2 * o.y(),
+        );
+        let side = nearest_root(roots, t_min, t_max, |t| {
+            let p = o + t * d;
+            p.y() <= self.height && phi_of(p) <= self.phi_max
+        })
+        .map(|t| {
+            let p = o + t * d;
+            LocalHit {
+                t,
+                normal: Vec3::new(2.0 * h * p.x(), -r2, 2.0 * h * p.z()),
+                u: phi_of(p) / self.phi_max,
+                v: p.y() / self.height,
+            }
+        });
+        let lid = if self.capped {
+            hit_cap(&local, h, self.radius, self.phi_max, true, t_min, t_max)
+        } else {
+            None
+        };
+        let hit = closest([side, lid, None])?;
+        Some(record(r, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(
+            self.base + Vec3::new(-r, 0.0, -r),
+            self.base + Vec3::new(r, self.height, r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::Color;
+
+    fn grey() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray::new(
+            Point3::new(origin.0, origin.1, origin.2),
+            Vec3::new(direction.0, direction.1, direction.2),
+            0.0,
+        )
+    }
+
+    #[test]
+    fn test_cylinder() {
+        let base = Point3::new(0.0, 1.0, 0.0);
+        let open = Cylinder::new(base, 1.0, 2.0, 2.0 * PI, false, grey());
+        let rec = open
+            .hit(
+                &ray((5.0, 2.0, 0.0), (-1.0, 0.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12);
+        assert!(rec.front_face && (rec.normal.x() - 1.0).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+
+        // straight down the axis only the caps are in the way
+        let down = ray((0.0, 5.0, 0.0), (0.0, -1.0, 0.0));
+        assert!(open.hit(&down, 0.001, f64::INFINITY).is_none());
+        let capped = Cylinder::new(base, 1.0, 2.0, 2.0 * PI, true, grey());
+        let rec = capped.hit(&down, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12 && (rec.normal.y() - 1.0).abs() < 1e-12);
+
+        // half a cylinder, the +z side: from +x the ray passes the cut
+        // and hits the inside of the far wall
+        let half = Cylinder::new(base, 1.0, 2.0, PI, false, grey());
+        let r = ray((5.0, 2.0, -0.5), (-1.0, 0.0, 0.0));
+        assert!(half.hit(&r, 0.001, f64::INFINITY).is_none());
+        let r = ray((5.0, 2.0, 0.5), (-1.0, 0.0, 0.0));
+        assert!(
+            (half.hit(&r, 0.001, f64::INFINITY).unwrap().t - (5.0 - 0.75f64.sqrt())).abs() < 1e-9
+        );
+        let r = ray((0.0, 2.0, -5.0), (0.0, 0.0, 1.0));
+        let rec = half.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-12 && !rec.front_face);
+    }
+
+    #[test]
+    fn test_short_direction() {
+        // rays of test_cylinder with directions 1e-8 long: t grows by 1e8,
+        // the points stay put
+        let base = Point3::new(0.0, 1.0, 0.0);
+        let capped = Cylinder::new(base, 1.0, 2.0, 2.0 * PI, true, grey());
+        let rec = capped
+            .hit(
+                &ray((5.0, 2.0, 0.0), (-1.0e-8, 0.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 4.0e8).abs() < 1e-4 && (rec.p.x() - 1.0).abs() < 1e-12);
+        let rec = capped
+            .hit(
+                &ray((0.0, 5.0, 0.0), (0.0, -1.0e-8, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.p.y() - 3.0).abs() < 1e-12 && (rec.normal.y() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cone() {
+        let cone = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0, 2.0 * PI, true, grey());
+        // halfway up the radius is 0.5, the normal leans 45 degrees up
+        let rec = cone
+            .hit(
+                &ray((2.0, 0.5, 0.0), (-1.0, 0.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-12);
+        assert!((rec.normal.x() - rec.normal.y()).abs() < 1e-12 && rec.normal.y() > 0.0);
+        // above the apex the other nappe is ignored
+        assert!(cone
+            .hit(
+                &ray((2.0, 1.5, 0.0), (-1.0, 0.0, 0.0)),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        let rec = cone
+            .hit(
+                &ray((0.2, -1.0, 0.0), (0.0, 1.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12 && rec.front_face);
+    }
+
+    #[test]
+    fn test_ellipsoid_matches_sphere() {
+        let center = Point3::new(0.5, -0.2, -3.0);
+        let sphere = Sphere::new(center, 0.7, grey());
+        let ellipsoid = Ellipsoid::new(center, Vec3::new(0.7, 0.7, 0.7), 2.0 * PI, grey());
+        for k in 0..50 {
+            let r = ray((0.0, 0.0, 0.0), (0.01 * k as f64, -0.005 * k as f64, -1.0));
+            let a = sphere.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let b = ellipsoid.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            match (a, b) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9),
+                (None, None) => {}
+                _ => panic!("sphere and ellipsoid disagree for ray {}", k),
+            }
+        }
+
+        // squashed: 2 wide along x, normals follow the gradient
+        let e = Ellipsoid::new(center, Vec3::new(2.0, 1.0, 1.0), 2.0 * PI, grey());
+        let rec = e
+            .hit(
+                &ray((0.5, 5.0, -3.0), (0.0, -1.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.p.y() - (-0.2 + 1.0)).abs() < 1e-9);
+        assert!((rec.v - 1.0).abs() < 1e-9);
+        assert!((e.bounding_box().unwrap().max().x() - 2.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_paraboloid() {
+        let bowl = Paraboloid::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            1.0,
+            2.0 * PI,
+            false,
+            grey(),
+        );
+        // vertex at the bottom, y = x^2 + z^2
+        let rec = bowl
+            .hit(
+                &ray((0.5, 5.0, 0.0), (0.0, -1.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.p.y() - 0.25).abs() < 1e-12);
+        assert!(!rec.front_face);
+        assert!(bowl
+            .hit(
+                &ray((1.5, 5.0, 0.0), (0.0, -1.0, 0.0)),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        let lidded = Paraboloid::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0, 2.0 * PI, true, grey());
+        let rec = lidded
+            .hit(
+                &ray((0.5, 5.0, 0.0), (0.0, -1.0, 0.0)),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12 && rec.front_face);
+    }
+}
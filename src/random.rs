@@ -0,0 +1,38 @@
+//! Per-thread random numbers that can be seeded for reproducible renders.
+
+use std::{cell::RefCell, ops::Range};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Reseeds the generator of the current thread.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// [0, 1)
+pub fn random_double() -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+pub fn random_range(r: Range<f64>) -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(r))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_is_reproducible() {
+        seed(42);
+        let a: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        seed(42);
+        let b: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        assert_eq!(a, b);
+        assert!(a.iter().all(|x| (0.0..1.0).contains(x)));
+    }
+}
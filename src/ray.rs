@@ -3,13 +3,16 @@ use super::vec::{Point3, Vec3};
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
+    //moment within the shutter interval, for moving objects
+    tm: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Point3, direction: Vec3) -> Ray {
+    pub fn new(origin: Point3, direction: Vec3, time: f64) -> Ray {
         Ray {
             orig: origin,
             dir: direction,
+            tm: time,
         }
     }
 
@@ -19,6 +22,9 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.dir
     }
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
     pub fn at(self, t: f64) -> Vec3 {
         self.orig + self.dir * t
     }
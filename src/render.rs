@@ -0,0 +1,346 @@
+use std::{
+    io::{stderr, Write},
+    sync::{Arc, Mutex},
+};
+
+use rayon::prelude::*;
+
+use super::environment::Environment;
+use super::hit::{Hit, HitRecord};
+use super::light::LightList;
+use super::output::Framebuffer;
+use super::random::{self, random_double};
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec::Color;
+
+/// Radiance arriving along `r`, following one path of at most `max_depth`
+/// hits. From bounce `min_bounces` on, Russian roulette ends the path with
+/// a chance that grows as its throughput drops, and scales up the paths
+/// that survive, so the estimate stays unbiased.
+pub fn ray_color(
+    r: &Ray,
+    world: &dyn Hit,
+    environment: &dyn Environment,
+    lights: &LightList,
+    max_depth: u64,
+    min_bounces: u64,
+) -> Color {
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+    //density of `ray` when it was sampled from a BSDF whose hit point also
+    //sampled the lights and the environment, so whatever emission it finds
+    //is weighted by MIS
+    let mut bsdf_pdf: Option<f64> = None;
+
+    for bounce in 0..max_depth {
+        let Some(rec) = world.hit(&ray, 0.001, f64::INFINITY) else {
+            //no hit, set color
+            let mut radiance = environment.radiance(ray.direction());
+            if let Some(pdf) = bsdf_pdf {
+                radiance = power_heuristic(pdf, environment.pdf(ray.direction())) * radiance;
+            }
+            color += throughput * radiance;
+            break;
+        };
+
+        let mut emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+        //the light density costs a ray per light, so only emitters ask for it
+        if let Some(pdf) = bsdf_pdf.filter(|_| rec.material.is_emissive()) {
+            emitted = power_heuristic(pdf, lights.pdf(&ray, rec.t)) * emitted;
+        }
+        color += throughput * emitted;
+
+        let Some(sample) = rec.material.sample(&ray, &rec) else {
+            break;
+        };
+        if sample.is_delta {
+            //specular, the next hit or miss counts in full
+            bsdf_pdf = None;
+        } else {
+            let direct = sample_lights(&ray, &rec, world, lights)
+                + sample_environment(&ray, &rec, world, environment);
+            color += throughput * direct;
+            bsdf_pdf = Some(sample.pdf);
+        }
+        throughput = throughput * sample.weight;
+        ray = Ray::new(rec.p, sample.direction, ray.time());
+
+        if bounce + 1 >= min_bounces {
+            let survive = throughput
+                .x()
+                .max(throughput.y())
+                .max(throughput.z())
+                .min(1.0);
+            if random_double() >= survive {
+                break;
+            }
+            throughput /= survive;
+        }
+    }
+    color
+}
+
+//one shadow ray towards a point on one light, counting that light's
+//emission if no surface is in front; media in between dim it by their
+//transmittance, the same as for the environment
+fn sample_lights(r_in: &Ray, rec: &HitRecord, world: &dyn Hit, lights: &LightList) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let Some((light, direction, light_pdf)) = lights.sample(rec.p) else {
+        return black;
+    };
+    let bsdf_pdf = rec.material.pdf(r_in, rec, direction);
+    if bsdf_pdf == 0.0 {
+        return black;
+    }
+    let shadow = Ray::new(rec.p, direction, r_in.time());
+    let Some(light_rec) = light.hit(&shadow, 0.001, f64::INFINITY) else {
+        return black;
+    };
+    //stop just short of the light, which is part of the world too
+    let transmittance = world.transmittance(&shadow, 0.001, light_rec.t * (1.0 - 1e-6));
+    if transmittance == 0.0 {
+        return black;
+    }
+    let emitted = light_rec
+        .material
+        .emitted(light_rec.u, light_rec.v, &light_rec.p);
+    let weight = power_heuristic(light_pdf, bsdf_pdf);
+    (weight * transmittance / light_pdf) * rec.material.eval(r_in, rec, direction) * emitted
+}
+
+//one shadow ray towards an importance sampled environment direction
+fn sample_environment(
+    r_in: &Ray,
+    rec: &HitRecord,
+    world: &dyn Hit,
+    environment: &dyn Environment,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let Some((direction, light_pdf)) = environment.sample() else {
+        return black;
+    };
+    let bsdf_pdf = rec.material.pdf(r_in, rec, direction);
+    if bsdf_pdf == 0.0 {
+        return black;
+    }
+    let shadow = Ray::new(rec.p, direction, r_in.time());
+    let transmittance = world.transmittance(&shadow, 0.001, f64::INFINITY);
+    if transmittance == 0.0 {
+        return black;
+    }
+    let weight = power_heuristic(light_pdf, bsdf_pdf);
+    (weight * transmittance / light_pdf)
+        * rec.material.eval(r_in, rec, direction)
+        * environment.radiance(direction)
+}
+
+/// MIS weight of a sample drawn with density `f` that the strategy with
+/// density `g` could also have produced.
+pub fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    if f2 + g2 == 0.0 {
+        0.0
+    } else {
+        f2 / (f2 + g2)
+    }
+}
+
+/// Renders rows in parallel into the averaged linear radiance.
+/// With a seed every row reseeds its thread's generator, so the image does
+/// not depend on how rayon schedules the rows.
+pub fn render(scene: &Scene, seed: Option<u64>) -> Framebuffer {
+    let world = &scene.world;
+    let cam = &scene.camera;
+    let image_width = scene.settings.image_width;
+    let image_height = scene.image_height;
+    let samples_per_pixel = scene.settings.samples_per_pixel;
+    let max_depth = scene.settings.max_depth;
+    let min_bounces = scene.settings.min_bounces;
+
+    let output_buffer = Arc::new(Mutex::new(vec![
+        Color::new(0.0, 0.0, 0.0);
+        (image_width * image_height) as usize
+    ]));
+
+    (0..image_height as usize)
+        .collect::<Vec<usize>>()
+        .par_iter()
+        //.rev()
+        .for_each_with(output_buffer.clone(), |output_buffer, &j| {
+            eprint!(
+                "\r{:5.2}%",
+                (image_height as usize - j) as f64 * 100.0 / image_height as f64
+            );
+            stderr().flush().unwrap();
+
+            if let Some(seed) = seed {
+                random::seed(seed ^ (j as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            }
+
+            let mut line_colors = Vec::with_capacity(image_width as usize);
+
+            for i in 0..image_width {
+                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                for _ in 0..samples_per_pixel {
+                    let random_u = random_double();
+                    let random_v = random_double();
+
+                    let u = ((i as f64) + random_u) / ((image_width - 1) as f64);
+                    let v = ((j as f64) + random_v) / ((image_height - 1) as f64);
+
+                    let r = cam.get_ray(u, v);
+                    pixel_color += ray_color(
+                        &r,
+                        world,
+                        scene.environment.as_ref(),
+                        &scene.lights,
+                        max_depth,
+                        min_bounces,
+                    );
+                }
+                line_colors.push(pixel_color / samples_per_pixel as f64);
+            }
+            output_buffer.lock().unwrap()[(image_height as usize - 1 - j) * image_width as usize
+                ..(image_height as usize - 1 - j + 1) * image_width as usize]
+                .copy_from_slice(&line_colors);
+        });
+
+    eprintln!("\r  Done!  ");
+    let pixels = output_buffer.lock().unwrap().clone();
+    Framebuffer {
+        width: image_width as usize,
+        height: image_height as usize,
+        pixels,
+        extra_channels: Vec::new(),
+    }
+}
+
+/// Depth ("Z") and world-space normal ("N.X", "N.Y", "N.Z") of the first hit
+/// through each pixel center, for compositing and denoising.
+pub fn render_aovs(scene: &Scene) -> Vec<(String, Vec<f32>)> {
+    let width = scene.settings.image_width as usize;
+    let height = scene.image_height as usize;
+
+    let rows: Vec<Vec<[f32; 4]>> = (0..height)
+        .into_par_iter()
+        .map(|row| {
+            let j = height - 1 - row;
+            (0..width)
+                .map(|i| {
+                    let u = (i as f64 + 0.5) / ((width - 1) as f64);
+                    let v = (j as f64 + 0.5) / ((height - 1) as f64);
+                    let r = scene.camera.get_center_ray(u, v);
+                    match scene.world.hit(&r, 0.001, f64::INFINITY) {
+                        Some(rec) => [
+                            (rec.t * r.direction().length()) as f32,
+                            rec.normal.x() as f32,
+                            rec.normal.y() as f32,
+                            rec.normal.z() as f32,
+                        ],
+                        None => [f32::INFINITY, 0.0, 0.0, 0.0],
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    ["Z", "N.X", "N.Y", "N.Z"]
+        .iter()
+        .enumerate()
+        .map(|(k, name)| {
+            let plane = rows.iter().flatten().map(|values| values[k]).collect();
+            (name.to_string(), plane)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SolidColor;
+    use crate::hit::World;
+    use crate::light::Light;
+    use crate::material::{DiffuseLight, Isotropic, Lambertian, Scatter};
+    use crate::medium::ConstantMedium;
+    use crate::quad::{BoxShape, Quad};
+    use crate::sphere::Sphere;
+    use crate::vec::{Point3, Vec3};
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        // everything scattered off a convex object escapes to the sky,
+        // so each path is worth exactly the albedo
+        let world: World = vec![Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -2.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ))];
+        let sky = SolidColor::new(Color::new(1.0, 1.0, 1.0));
+        let lights = LightList::default();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+
+        let exact = ray_color(&r, &world, &sky, &lights, 20, 5);
+        assert!((exact.y() - 0.5).abs() < 1e-12);
+
+        // from the first bounce half the paths end and the rest count double
+        let n = 20000;
+        let mut ended = 0;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let c = ray_color(&r, &world, &sky, &lights, 20, 0).y();
+            if c == 0.0 {
+                ended += 1;
+            }
+            total += c;
+        }
+        assert!((ended as f64 / n as f64 - 0.5).abs() < 0.02);
+        assert!((total / n as f64 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_light_through_fog() {
+        // a tiny lamp straight above, seen through a slab of fog 1 thick:
+        // every shadow ray is dimmed by exp(-1), none is cut off at random
+        let lamp = Arc::new(Quad::new(
+            Point3::new(-0.005, 2.0, -0.005),
+            Vec3::new(0.01, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.01),
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        ));
+        let lights = LightList::new(vec![lamp.clone() as Arc<dyn Light>]);
+        let fog = Arc::new(ConstantMedium::new(
+            Arc::new(BoxShape::new(
+                Point3::new(-10.0, 0.5, -10.0),
+                Point3::new(10.0, 1.5, 10.0),
+                Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            )),
+            1.0,
+            Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+        ));
+        let clear: World = vec![lamp.clone()];
+        let foggy: World = vec![lamp, fog];
+
+        let floor: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let rec = HitRecord {
+            p: Point3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: true,
+            material: floor,
+        };
+        let r_in = Ray::new(Point3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0), 0.0);
+        for i in 0..50 {
+            random::seed(i);
+            let without = sample_lights(&r_in, &rec, &clear, &lights).y();
+            random::seed(i);
+            let with = sample_lights(&r_in, &rec, &foggy, &lights).y();
+            assert!(without > 0.0);
+            assert!((with / without - (-1.0f64).exp()).abs() < 1e-3);
+        }
+    }
+}
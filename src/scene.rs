@@ -0,0 +1,1751 @@
+//! JSON scene description.
+//!
+//! ```json
+//! {
+//!   "camera": { "lookfrom": [-2, 2, 1], "lookat": [0, 0, -1], "vfov": 20, "aspect_ratio": 1.7778,
+//!               "aperture": 0.1, "autofocus": true },
+//!   "render": { "image_width": 256, "samples_per_pixel": 100, "max_depth": 20, "min_bounces": 3 },
+//!   "environment": { "type": "solid", "color": [0, 0, 0] },
+//!   "materials": { "glass": { "type": "dielectric", "ir": 1.5 } },
+//!   "objects": [ { "type": "sphere", "center": [0, 0, -1], "radius": 0.5, "material": "glass" } ]
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::environment::{Environment, EnvironmentMap, Gradient, SolidColor};
+use crate::grid::DensityGrid;
+use crate::hit::{Hit, World};
+use crate::input::{self, Wrap};
+use crate::instance::Instance;
+use crate::light::{Light, LightList};
+use crate::material::{
+    Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Metal, Scatter,
+};
+use crate::medium::{ConstantMedium, GridMedium};
+use crate::obj::{self, ObjError};
+use crate::plane::{Disk, Plane};
+use crate::quad::{BoxShape, Quad};
+use crate::quadric::{Cone, Cylinder, Ellipsoid, Paraboloid};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{
+    self, CheckerTexture, CloudTexture, ImageTexture, MarbleTexture, NoiseTexture, Texture,
+    WoodTexture,
+};
+use crate::torus::Torus;
+use crate::transform::{Mat4, Transform};
+use crate::triangle::Triangle;
+use crate::vec::{Color, Vec3};
+
+const BUILTIN_SCENES: [(&str, &str); 3] = [
+    (
+        "three_spheres",
+        include_str!("../scenes/three_spheres.json"),
+    ),
+    ("cornell_box", include_str!("../scenes/cornell_box.json")),
+    ("procedural", include_str!("../scenes/procedural.json")),
+];
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { file: PathBuf, source: io::Error },
+    //`path` is the offending key, e.g. `objects[2].material`
+    Invalid { path: String, message: String },
+    Mesh { path: String, source: ObjError },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { file, source } => write!(f, "{}: {}", file.display(), source),
+            SceneError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+            SceneError::Mesh { path, source } => write!(f, "{}: {}", path, source),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Invalid { .. } => None,
+            SceneError::Mesh { source, .. } => Some(source),
+        }
+    }
+}
+
+fn invalid(path: impl Into<String>, message: impl Into<String>) -> SceneError {
+    SceneError::Invalid {
+        path: path.into(),
+        message: message.into(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraSpec,
+    #[serde(default)]
+    pub render: RenderSettings,
+    //the book's sky gradient when omitted
+    pub environment: Option<EnvironmentSpec>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialSpec>,
+    //objects built once and placed any number of times by `instance` objects
+    #[serde(default)]
+    pub prototypes: BTreeMap<String, ObjectSpec>,
+    #[serde(default)]
+    pub objects: Vec<ObjectSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraSpec {
+    pub lookfrom: [f64; 3],
+    pub lookat: [f64; 3],
+    #[serde(default = "default_vup")]
+    pub vup: [f64; 3],
+    pub vfov: f64,
+    pub aspect_ratio: f64,
+    //lens diameter, 0 keeps everything sharp
+    #[serde(default)]
+    pub aperture: f64,
+    //distance from lookfrom to lookat when omitted
+    pub focus_dist: Option<f64>,
+    //focus on whatever is in the image center, replaces focus_dist
+    #[serde(default)]
+    pub autofocus: bool,
+    //open and close time, moving objects blur in between
+    #[serde(default)]
+    pub shutter: [f64; 2],
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields, default)]
+pub struct RenderSettings {
+    pub image_width: u64,
+    //derived from the camera aspect ratio when omitted
+    pub image_height: Option<u64>,
+    pub samples_per_pixel: u64,
+    pub max_depth: u64,
+    //bounces before Russian roulette may end a path
+    pub min_bounces: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            image_width: 256,
+            image_height: None,
+            samples_per_pixel: 1000,
+            max_depth: 20,
+            min_bounces: 3,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialSpec {
+    Lambertian { albedo: TextureSpec },
+    Metal { albedo: TextureSpec, fuzz: f64 },
+    Dielectric { ir: f64 },
+    DiffuseLight { emit: [f64; 3] },
+    //phase function for constant_medium objects
+    Isotropic { albedo: TextureSpec },
+    //anisotropic phase function, g > 0 scatters forward
+    HenyeyGreenstein { albedo: TextureSpec, g: f64 },
+}
+
+/// A plain `[r, g, b]` or one of the texture objects.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum TextureSpec {
+    Color([f64; 3]),
+    Texture(TextureKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureKind {
+    Solid {
+        color: [f64; 3],
+    },
+    //cubes of edge `scale` in world space
+    Checker {
+        scale: f64,
+        even: Box<TextureSpec>,
+        odd: Box<TextureSpec>,
+    },
+    //PNG, JPEG, HDR or EXR, relative to the scene file
+    Image {
+        path: String,
+        #[serde(default)]
+        wrap: WrapSpec,
+    },
+    //procedural, the same seed gives the same pattern
+    Noise {
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_white")]
+        base: [f64; 3],
+        #[serde(default)]
+        vein: [f64; 3],
+    },
+    Wood {
+        //rings per unit
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_light_wood")]
+        light: [f64; 3],
+        #[serde(default = "default_dark_wood")]
+        dark: [f64; 3],
+    },
+    Clouds {
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_top")]
+        sky: [f64; 3],
+        #[serde(default = "default_white")]
+        cloud: [f64; 3],
+    },
+}
+
+fn default_white() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_wood() -> [f64; 3] {
+    [0.8, 0.6, 0.35]
+}
+
+fn default_dark_wood() -> [f64; 3] {
+    [0.45, 0.25, 0.1]
+}
+
+fn default_octaves() -> u32 {
+    6
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapSpec {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EnvironmentSpec {
+    Solid {
+        color: [f64; 3],
+    },
+    Gradient {
+        #[serde(default = "default_bottom")]
+        bottom: [f64; 3],
+        #[serde(default = "default_top")]
+        top: [f64; 3],
+    },
+    //equirectangular .hdr or .exr, relative to the scene file; rotation in degrees about +y
+    Image {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        #[serde(default)]
+        rotation: f64,
+    },
+}
+
+fn default_bottom() -> [f64; 3] {
+    default_white()
+}
+
+fn default_top() -> [f64; 3] {
+    [0.5, 0.7, 1.0]
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectSpec {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        #[serde(default = "default_true")]
+        double_sided: bool,
+        material: String,
+    },
+    //parallelogram with corner q and edges u, v, facing along u x v
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    //axis-aligned, between opposite corners
+    #[serde(rename = "box")]
+    BoxShape {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    //infinite, (u, v) repeat every tile_size units when given
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        tile_size: Option<f64>,
+        material: String,
+    },
+    //an annulus when inner_radius > 0
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        #[serde(default)]
+        inner_radius: f64,
+        material: String,
+    },
+    //quadrics stand on base with their axis along +y; phi_max, in degrees,
+    //sweeps them partially around it
+    Cylinder {
+        base: [f64; 3],
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_phi_max")]
+        phi_max: f64,
+        #[serde(default)]
+        capped: bool,
+        material: String,
+    },
+    //apex height above base, capped closes the bottom
+    Cone {
+        base: [f64; 3],
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_phi_max")]
+        phi_max: f64,
+        #[serde(default)]
+        capped: bool,
+        material: String,
+    },
+    //semi-axes along x, y and z
+    Ellipsoid {
+        center: [f64; 3],
+        radii: [f64; 3],
+        #[serde(default = "default_phi_max")]
+        phi_max: f64,
+        material: String,
+    },
+    //bowl with its vertex at base, radius wide at height; capped puts a lid on
+    Paraboloid {
+        base: [f64; 3],
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_phi_max")]
+        phi_max: f64,
+        #[serde(default)]
+        capped: bool,
+        material: String,
+    },
+    //ring in the plane y = center.y, a tube of minor_radius around a circle of major_radius
+    Torus {
+        center: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    //moves from center0 at time0 to center1 at time1
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    //OBJ file, relative to the scene file; `material` is used for faces without `usemtl`
+    Mesh {
+        path: String,
+        material: String,
+    },
+    //a prototype placed by transform steps applied in order; material, when
+    //given, replaces the prototype's own
+    Instance {
+        of: String,
+        #[serde(default)]
+        transform: Vec<TransformSpec>,
+        material: Option<String>,
+    },
+    //fog filling a closed boundary object, whose own material is not used
+    ConstantMedium {
+        boundary: Box<ObjectSpec>,
+        density: f64,
+        material: String,
+    },
+    //raw little-endian f32 densities, x fastest, stretched over the box min..max
+    GridMedium {
+        path: String,
+        resolution: [usize; 3],
+        min: [f64; 3],
+        max: [f64; 3],
+        #[serde(default = "default_density")]
+        density: f64,
+        material: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformSpec {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    //degrees, counter-clockwise with the axis pointing at the viewer
+    Rotate { axis: [f64; 3], degrees: f64 },
+    //row-major, the bottom row must be 0, 0, 0, 1
+    Matrix([[f64; 4]; 4]),
+}
+
+impl TransformSpec {
+    fn build(&self, path: &str) -> Result<Transform, SceneError> {
+        match self {
+            TransformSpec::Translate(offset) => {
+                check_finite(&format!("{}.translate", path), offset)?;
+                Ok(Transform::translate(vec3(*offset)))
+            }
+            TransformSpec::Scale(factors) => {
+                check_finite(&format!("{}.scale", path), factors)?;
+                Transform::scale(vec3(*factors))
+                    .ok_or_else(|| invalid(format!("{}.scale", path), "must be non-zero"))
+            }
+            TransformSpec::Rotate { axis, degrees } => {
+                check_finite(&format!("{}.rotate.axis", path), axis)?;
+                if vec3(*axis).near_zero() {
+                    return Err(invalid(format!("{}.rotate.axis", path), "must be non-zero"));
+                }
+                if !degrees.is_finite() {
+                    return Err(invalid(
+                        format!("{}.rotate.degrees", path),
+                        "must be finite",
+                    ));
+                }
+                Ok(Transform::rotate(vec3(*axis), *degrees))
+            }
+            TransformSpec::Matrix(m) => {
+                let path = format!("{}.matrix", path);
+                check_finite(&path, m.as_flattened())?;
+                if m[3] != [0.0, 0.0, 0.0, 1.0] {
+                    return Err(invalid(path, "bottom row must be [0, 0, 0, 1]"));
+                }
+                Transform::new(Mat4::new(*m)).ok_or_else(|| invalid(path, "must be invertible"))
+            }
+        }
+    }
+}
+
+fn default_phi_max() -> f64 {
+    360.0
+}
+
+fn default_density() -> f64 {
+    1.0
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+pub struct Scene {
+    pub world: BvhNode,
+    pub camera: Camera,
+    pub environment: Arc<dyn Environment>,
+    //emissive spheres, quads, boxes, disks and triangles, sampled directly
+    pub lights: LightList,
+    pub settings: RenderSettings,
+    pub image_height: u64,
+}
+
+impl SceneFile {
+    pub fn from_json(source: &str) -> Result<SceneFile, SceneError> {
+        let de = &mut serde_json::Deserializer::from_str(source);
+        serde_path_to_error::deserialize(de).map_err(|err| {
+            let path = err.path().to_string();
+            let inner = err.into_inner();
+            // serde_json appends " at line L column C" itself
+            invalid(path, inner.to_string())
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<SceneFile, SceneError> {
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            file: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_json(&source)
+    }
+
+    pub fn builtin(name: &str) -> Option<SceneFile> {
+        BUILTIN_SCENES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| Self::from_json(source).expect("builtin scene must parse"))
+    }
+
+    pub fn builtin_names() -> Vec<&'static str> {
+        BUILTIN_SCENES.iter().map(|(n, _)| *n).collect()
+    }
+
+    /// Relative mesh paths are resolved against `base_dir`.
+    pub fn build(&self, base_dir: &Path) -> Result<Scene, SceneError> {
+        self.render.validate()?;
+        let image_height = match self.render.image_height {
+            Some(h) if h < 2 => return Err(invalid("render.image_height", "must be at least 2")),
+            Some(h) => h,
+            None => {
+                let h = ((self.render.image_width as f64) / self.camera.aspect_ratio) as u64;
+                if h < 2 {
+                    return Err(invalid(
+                        "render.image_width",
+                        "too small for the aspect ratio, image height must be at least 2",
+                    ));
+                }
+                h
+            }
+        };
+
+        let environment = match &self.environment {
+            Some(spec) => spec.build(base_dir)?,
+            None => Arc::new(Gradient::sky()),
+        };
+
+        let mut materials: BTreeMap<&str, Arc<dyn Scatter>> = BTreeMap::new();
+        for (name, spec) in self.materials.iter() {
+            materials.insert(name, spec.build(&format!("materials.{}", name), base_dir)?);
+        }
+
+        //placed only through instances, so none of them is a light
+        let mut prototypes: BTreeMap<&str, Arc<dyn Hit>> = BTreeMap::new();
+        for (name, spec) in self.prototypes.iter() {
+            let object = spec.build(
+                &format!("prototypes.{}", name),
+                &materials,
+                &BTreeMap::new(),
+                base_dir,
+                &mut Vec::new(),
+            )?;
+            prototypes.insert(name, object);
+        }
+
+        let mut world = World::new();
+        let mut lights = Vec::new();
+        for (i, spec) in self.objects.iter().enumerate() {
+            world.push(spec.build(
+                &format!("objects[{}]", i),
+                &materials,
+                &prototypes,
+                base_dir,
+                &mut lights,
+            )?);
+        }
+
+        let world = BvhNode::new(world);
+        let camera = self.camera.build(&world)?;
+
+        Ok(Scene {
+            world,
+            camera,
+            environment,
+            lights: LightList::new(lights),
+            settings: self.render,
+            image_height,
+        })
+    }
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+//a x b is negligible next to |a| |b|, whatever the scale of the scene:
+//the two are parallel or one of them vanishes
+fn parallel(a: Vec3, b: Vec3) -> bool {
+    a.cross(b).length() <= 1e-12 * a.length() * b.length()
+}
+
+//radius and height of a quadric, and phi_max converted to radians
+fn check_sweep(path: &str, radius: f64, height: f64, phi_max: f64) -> Result<f64, SceneError> {
+    if !(radius > 0.0 && radius.is_finite()) {
+        return Err(invalid(format!("{}.radius", path), "must be > 0"));
+    }
+    if !(height > 0.0 && height.is_finite()) {
+        return Err(invalid(format!("{}.height", path), "must be > 0"));
+    }
+    check_phi_max(path, phi_max)
+}
+
+fn check_phi_max(path: &str, phi_max: f64) -> Result<f64, SceneError> {
+    if !(phi_max > 0.0 && phi_max <= 360.0) {
+        return Err(invalid(format!("{}.phi_max", path), "must be in (0, 360]"));
+    }
+    Ok(phi_max.to_radians())
+}
+
+fn check_finite(path: &str, v: &[f64]) -> Result<(), SceneError> {
+    if v.iter().all(|x| x.is_finite()) {
+        Ok(())
+    } else {
+        Err(invalid(path, "must be finite"))
+    }
+}
+
+fn check_color(path: &str, c: [f64; 3]) -> Result<Color, SceneError> {
+    if c.iter().all(|x| x.is_finite() && *x >= 0.0) {
+        Ok(vec3(c))
+    } else {
+        Err(invalid(path, "color components must be finite and >= 0"))
+    }
+}
+
+impl CameraSpec {
+    fn build(&self, world: &dyn Hit) -> Result<Camera, SceneError> {
+        check_finite("camera.lookfrom", &self.lookfrom)?;
+        check_finite("camera.lookat", &self.lookat)?;
+        check_finite("camera.vup", &self.vup)?;
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return Err(invalid("camera.vfov", "must be in (0, 180) degrees"));
+        }
+        if !(self.aspect_ratio > 0.0 && self.aspect_ratio.is_finite()) {
+            return Err(invalid("camera.aspect_ratio", "must be > 0"));
+        }
+        let lookfrom = vec3(self.lookfrom);
+        let lookat = vec3(self.lookat);
+        let vup = vec3(self.vup);
+        if (lookfrom - lookat).near_zero() {
+            return Err(invalid("camera.lookat", "must differ from lookfrom"));
+        }
+        if vup.cross(lookfrom - lookat).near_zero() {
+            return Err(invalid(
+                "camera.vup",
+                "must not be parallel to the view direction",
+            ));
+        }
+        check_finite("camera.shutter", &self.shutter)?;
+        if self.shutter[0] > self.shutter[1] {
+            return Err(invalid("camera.shutter", "must not close before it opens"));
+        }
+        if !(self.aperture >= 0.0 && self.aperture.is_finite()) {
+            return Err(invalid("camera.aperture", "must be >= 0"));
+        }
+        let focus_dist = match self.focus_dist {
+            Some(_) if self.autofocus => {
+                return Err(invalid(
+                    "camera.focus_dist",
+                    "cannot be combined with autofocus",
+                ))
+            }
+            Some(d) if !(d > 0.0 && d.is_finite()) => {
+                return Err(invalid("camera.focus_dist", "must be > 0"))
+            }
+            Some(d) => d,
+            None => (lookfrom - lookat).length(),
+        };
+        let camera = |aperture, focus_dist| {
+            Camera::new(
+                lookfrom,
+                lookat,
+                vup,
+                self.vfov,
+                self.aspect_ratio,
+                aperture,
+                focus_dist,
+            )
+            .with_shutter(self.shutter[0], self.shutter[1])
+        };
+        let focus_dist = if self.autofocus {
+            //nothing in the center keeps the default distance
+            camera(0.0, focus_dist)
+                .autofocus(world)
+                .unwrap_or(focus_dist)
+        } else {
+            focus_dist
+        };
+        Ok(camera(self.aperture, focus_dist))
+    }
+}
+
+impl RenderSettings {
+    fn validate(&self) -> Result<(), SceneError> {
+        if self.image_width < 2 {
+            return Err(invalid("render.image_width", "must be at least 2"));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(invalid("render.samples_per_pixel", "must be > 0"));
+        }
+        if self.max_depth == 0 {
+            return Err(invalid("render.max_depth", "must be > 0"));
+        }
+        Ok(())
+    }
+}
+
+impl EnvironmentSpec {
+    fn build(&self, base_dir: &Path) -> Result<Arc<dyn Environment>, SceneError> {
+        Ok(match self {
+            EnvironmentSpec::Solid { color } => {
+                Arc::new(SolidColor::new(check_color("environment.color", *color)?))
+            }
+            EnvironmentSpec::Gradient { bottom, top } => Arc::new(Gradient::new(
+                check_color("environment.bottom", *bottom)?,
+                check_color("environment.top", *top)?,
+            )),
+            EnvironmentSpec::Image {
+                path,
+                intensity,
+                rotation,
+            } => {
+                if !(*intensity >= 0.0 && intensity.is_finite()) {
+                    return Err(invalid("environment.intensity", "must be >= 0"));
+                }
+                check_finite("environment.rotation", &[*rotation])?;
+                let file = base_dir.join(path);
+                let image =
+                    input::load_image(&file).map_err(|source| SceneError::Io { file, source })?;
+                Arc::new(EnvironmentMap::new(
+                    image,
+                    *intensity,
+                    rotation.to_radians(),
+                ))
+            }
+        })
+    }
+}
+
+impl TextureSpec {
+    fn build(&self, path: &str, base_dir: &Path) -> Result<Arc<dyn Texture>, SceneError> {
+        Ok(match self {
+            TextureSpec::Color(color) => {
+                Arc::new(texture::SolidColor::new(check_color(path, *color)?))
+            }
+            TextureSpec::Texture(TextureKind::Solid { color }) => Arc::new(
+                texture::SolidColor::new(check_color(&format!("{}.color", path), *color)?),
+            ),
+            TextureSpec::Texture(TextureKind::Checker { scale, even, odd }) => {
+                Arc::new(CheckerTexture::new(
+                    check_scale(path, *scale)?,
+                    even.build(&format!("{}.even", path), base_dir)?,
+                    odd.build(&format!("{}.odd", path), base_dir)?,
+                ))
+            }
+            TextureSpec::Texture(TextureKind::Image { path: file, wrap }) => {
+                let file = base_dir.join(file);
+                let image =
+                    input::load_image(&file).map_err(|source| SceneError::Io { file, source })?;
+                let wrap = match wrap {
+                    WrapSpec::Repeat => Wrap::Repeat,
+                    WrapSpec::Clamp => Wrap::Clamp,
+                    WrapSpec::Mirror => Wrap::Mirror,
+                };
+                Arc::new(ImageTexture::new(image, wrap, wrap))
+            }
+            TextureSpec::Texture(TextureKind::Noise { scale, seed }) => {
+                Arc::new(NoiseTexture::new(*seed, check_scale(path, *scale)?))
+            }
+            TextureSpec::Texture(TextureKind::Marble {
+                scale,
+                seed,
+                base,
+                vein,
+            }) => Arc::new(MarbleTexture::new(
+                *seed,
+                check_scale(path, *scale)?,
+                check_color(&format!("{}.base", path), *base)?,
+                check_color(&format!("{}.vein", path), *vein)?,
+            )),
+            TextureSpec::Texture(TextureKind::Wood {
+                scale,
+                seed,
+                light,
+                dark,
+            }) => Arc::new(WoodTexture::new(
+                *seed,
+                check_scale(path, *scale)?,
+                check_color(&format!("{}.light", path), *light)?,
+                check_color(&format!("{}.dark", path), *dark)?,
+            )),
+            TextureSpec::Texture(TextureKind::Clouds {
+                scale,
+                seed,
+                octaves,
+                sky,
+                cloud,
+            }) => {
+                if *octaves == 0 {
+                    return Err(invalid(format!("{}.octaves", path), "must be > 0"));
+                }
+                Arc::new(CloudTexture::new(
+                    *seed,
+                    check_scale(path, *scale)?,
+                    *octaves,
+                    check_color(&format!("{}.sky", path), *sky)?,
+                    check_color(&format!("{}.cloud", path), *cloud)?,
+                ))
+            }
+        })
+    }
+}
+
+fn check_scale(path: &str, scale: f64) -> Result<f64, SceneError> {
+    if scale > 0.0 && scale.is_finite() {
+        Ok(scale)
+    } else {
+        Err(invalid(format!("{}.scale", path), "must be > 0"))
+    }
+}
+
+impl MaterialSpec {
+    fn build(&self, path: &str, base_dir: &Path) -> Result<Arc<dyn Scatter>, SceneError> {
+        Ok(match self {
+            MaterialSpec::Lambertian { albedo } => Arc::new(Lambertian::textured(
+                albedo.build(&format!("{}.albedo", path), base_dir)?,
+            )),
+            MaterialSpec::Metal { albedo, fuzz } => {
+                let albedo = albedo.build(&format!("{}.albedo", path), base_dir)?;
+                if !(0.0..=1.0).contains(fuzz) {
+                    return Err(invalid(format!("{}.fuzz", path), "must be in [0, 1]"));
+                }
+                Arc::new(Metal::textured(albedo, *fuzz))
+            }
+            MaterialSpec::Dielectric { ir } => {
+                if !(*ir > 0.0 && ir.is_finite()) {
+                    return Err(invalid(format!("{}.ir", path), "must be > 0"));
+                }
+                Arc::new(Dielectric::new(*ir))
+            }
+            MaterialSpec::DiffuseLight { emit } => Arc::new(DiffuseLight::new(check_color(
+                &format!("{}.emit", path),
+                *emit,
+            )?)),
+            MaterialSpec::Isotropic { albedo } => Arc::new(Isotropic::textured(
+                albedo.build(&format!("{}.albedo", path), base_dir)?,
+            )),
+            MaterialSpec::HenyeyGreenstein { albedo, g } => {
+                let albedo = albedo.build(&format!("{}.albedo", path), base_dir)?;
+                if !(*g > -1.0 && *g < 1.0) {
+                    return Err(invalid(format!("{}.g", path), "must be in (-1, 1)"));
+                }
+                Arc::new(HenyeyGreenstein::textured(albedo, *g))
+            }
+        })
+    }
+}
+
+fn lookup(
+    materials: &BTreeMap<&str, Arc<dyn Scatter>>,
+    path: &str,
+    name: &str,
+) -> Result<Arc<dyn Scatter>, SceneError> {
+    materials.get(name).cloned().ok_or_else(|| {
+        invalid(
+            format!("{}.material", path),
+            format!("unknown material '{}'", name),
+        )
+    })
+}
+
+impl ObjectSpec {
+    fn build(
+        &self,
+        path: &str,
+        materials: &BTreeMap<&str, Arc<dyn Scatter>>,
+        prototypes: &BTreeMap<&str, Arc<dyn Hit>>,
+        base_dir: &Path,
+        lights: &mut Vec<Arc<dyn Light>>,
+    ) -> Result<Arc<dyn Hit>, SceneError> {
+        Ok(match self {
+            ObjectSpec::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                check_finite(&format!("{}.center", path), center)?;
+                if !(radius.is_finite() && *radius != 0.0) {
+                    return Err(invalid(format!("{}.radius", path), "must be non-zero"));
+                }
+                let material = lookup(materials, path, material)?;
+                let emissive = material.is_emissive();
+                let sphere = Arc::new(Sphere::new(vec3(*center), *radius, material));
+                if emissive {
+                    lights.push(sphere.clone());
+                }
+                sphere
+            }
+            ObjectSpec::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                check_finite(&format!("{}.center0", path), center0)?;
+                check_finite(&format!("{}.center1", path), center1)?;
+                check_finite(&format!("{}.time0", path), &[*time0])?;
+                check_finite(&format!("{}.time1", path), &[*time1])?;
+                if time0 >= time1 {
+                    return Err(invalid(
+                        format!("{}.time1", path),
+                        "must be later than time0",
+                    ));
+                }
+                if !(radius.is_finite() && *radius != 0.0) {
+                    return Err(invalid(format!("{}.radius", path), "must be non-zero"));
+                }
+                Arc::new(MovingSphere::new(
+                    vec3(*center0),
+                    vec3(*center1),
+                    *time0,
+                    *time1,
+                    *radius,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::Triangle {
+                vertices,
+                normals,
+                uvs,
+                double_sided,
+                material,
+            } => {
+                check_finite(&format!("{}.vertices", path), vertices.as_flattened())?;
+                let v = vertices.map(vec3);
+                if parallel(v[1] - v[0], v[2] - v[0]) {
+                    return Err(invalid(
+                        format!("{}.vertices", path),
+                        "triangle is degenerate",
+                    ));
+                }
+                let material = lookup(materials, path, material)?;
+                let emissive = material.is_emissive();
+                let mut tri = Triangle::new(v[0], v[1], v[2], material).double_sided(*double_sided);
+                if let Some(n) = normals {
+                    check_finite(&format!("{}.normals", path), n.as_flattened())?;
+                    tri = tri.with_normals(n.map(|n| vec3(n).normalized()));
+                }
+                if let Some(uv) = uvs {
+                    check_finite(&format!("{}.uvs", path), uv.as_flattened())?;
+                    tri = tri.with_uvs(uv.map(|t| (t[0], t[1])));
+                }
+                let tri = Arc::new(tri);
+                if emissive {
+                    lights.push(tri.clone());
+                }
+                tri
+            }
+            ObjectSpec::Quad { q, u, v, material } => {
+                check_finite(&format!("{}.q", path), q)?;
+                check_finite(&format!("{}.u", path), u)?;
+                check_finite(&format!("{}.v", path), v)?;
+                if parallel(vec3(*u), vec3(*v)) {
+                    return Err(invalid(format!("{}.v", path), "quad is degenerate"));
+                }
+                let material = lookup(materials, path, material)?;
+                let emissive = material.is_emissive();
+                let quad = Arc::new(Quad::new(vec3(*q), vec3(*u), vec3(*v), material));
+                if emissive {
+                    lights.push(quad.clone());
+                }
+                quad
+            }
+            ObjectSpec::Plane {
+                point,
+                normal,
+                tile_size,
+                material,
+            } => {
+                check_finite(&format!("{}.point", path), point)?;
+                check_finite(&format!("{}.normal", path), normal)?;
+                if vec3(*normal).near_zero() {
+                    return Err(invalid(format!("{}.normal", path), "must be non-zero"));
+                }
+                let mut plane = Plane::new(
+                    vec3(*point),
+                    vec3(*normal),
+                    lookup(materials, path, material)?,
+                );
+                if let Some(size) = tile_size {
+                    if !(*size > 0.0 && size.is_finite()) {
+                        return Err(invalid(format!("{}.tile_size", path), "must be > 0"));
+                    }
+                    plane = plane.tiled(*size);
+                }
+                Arc::new(plane)
+            }
+            ObjectSpec::Disk {
+                center,
+                normal,
+                radius,
+                inner_radius,
+                material,
+            } => {
+                check_finite(&format!("{}.center", path), center)?;
+                check_finite(&format!("{}.normal", path), normal)?;
+                if vec3(*normal).near_zero() {
+                    return Err(invalid(format!("{}.normal", path), "must be non-zero"));
+                }
+                if !(*radius > 0.0 && radius.is_finite()) {
+                    return Err(invalid(format!("{}.radius", path), "must be > 0"));
+                }
+                if !(0.0..*radius).contains(inner_radius) {
+                    return Err(invalid(
+                        format!("{}.inner_radius", path),
+                        "must be in [0, radius)",
+                    ));
+                }
+                let material = lookup(materials, path, material)?;
+                let emissive = material.is_emissive();
+                let disk = Arc::new(
+                    Disk::new(vec3(*center), vec3(*normal), *radius, material)
+                        .with_inner_radius(*inner_radius),
+                );
+                if emissive {
+                    lights.push(disk.clone());
+                }
+                disk
+            }
+            ObjectSpec::Cylinder {
+                base,
+                radius,
+                height,
+                phi_max,
+                capped,
+                material,
+            } => {
+                check_finite(&format!("{}.base", path), base)?;
+                let phi_max = check_sweep(path, *radius, *height, *phi_max)?;
+                Arc::new(Cylinder::new(
+                    vec3(*base),
+                    *radius,
+                    *height,
+                    phi_max,
+                    *capped,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::Cone {
+                base,
+                radius,
+                height,
+                phi_max,
+                capped,
+                material,
+            } => {
+                check_finite(&format!("{}.base", path), base)?;
+                let phi_max = check_sweep(path, *radius, *height, *phi_max)?;
+                Arc::new(Cone::new(
+                    vec3(*base),
+                    *radius,
+                    *height,
+                    phi_max,
+                    *capped,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::Ellipsoid {
+                center,
+                radii,
+                phi_max,
+                material,
+            } => {
+                check_finite(&format!("{}.center", path), center)?;
+                if !radii.iter().all(|r| *r > 0.0 && r.is_finite()) {
+                    return Err(invalid(format!("{}.radii", path), "must be > 0"));
+                }
+                let phi_max = check_phi_max(path, *phi_max)?;
+                Arc::new(Ellipsoid::new(
+                    vec3(*center),
+                    vec3(*radii),
+                    phi_max,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::Paraboloid {
+                base,
+                radius,
+                height,
+                phi_max,
+                capped,
+                material,
+            } => {
+                check_finite(&format!("{}.base", path), base)?;
+                let phi_max = check_sweep(path, *radius, *height, *phi_max)?;
+                Arc::new(Paraboloid::new(
+                    vec3(*base),
+                    *radius,
+                    *height,
+                    phi_max,
+                    *capped,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::Torus {
+                center,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                check_finite(&format!("{}.center", path), center)?;
+                if !(*major_radius > 0.0 && major_radius.is_finite()) {
+                    return Err(invalid(format!("{}.major_radius", path), "must be > 0"));
+                }
+                if !(*minor_radius > 0.0 && minor_radius <= major_radius) {
+                    return Err(invalid(
+                        format!("{}.minor_radius", path),
+                        "must be in (0, major_radius]",
+                    ));
+                }
+                Arc::new(Torus::new(
+                    vec3(*center),
+                    *major_radius,
+                    *minor_radius,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::BoxShape { min, max, material } => {
+                check_finite(&format!("{}.min", path), min)?;
+                check_finite(&format!("{}.max", path), max)?;
+                if (0..3).any(|i| min[i] >= max[i]) {
+                    return Err(invalid(format!("{}.max", path), "must be above min"));
+                }
+                let material = lookup(materials, path, material)?;
+                let emissive = material.is_emissive();
+                let shape = Arc::new(BoxShape::new(vec3(*min), vec3(*max), material));
+                if emissive {
+                    for side in shape.sides() {
+                        lights.push(side.clone());
+                    }
+                }
+                shape
+            }
+            ObjectSpec::Mesh {
+                path: file,
+                material,
+            } => {
+                let mesh = obj::load_obj(&base_dir.join(file), lookup(materials, path, material)?)
+                    .map_err(|source| SceneError::Mesh {
+                        path: format!("{}.path", path),
+                        source,
+                    })?;
+                Arc::new(mesh)
+            }
+            ObjectSpec::Instance {
+                of,
+                transform,
+                material,
+            } => {
+                let object = prototypes.get(of.as_str()).cloned().ok_or_else(|| {
+                    invalid(
+                        format!("{}.of", path),
+                        format!("unknown prototype '{}'", of),
+                    )
+                })?;
+                let mut placement = Transform::identity();
+                for (i, step) in transform.iter().enumerate() {
+                    placement = placement.then(&step.build(&format!("{}.transform[{}]", path, i))?);
+                }
+                let mut instance = Instance::new(object, placement);
+                if let Some(name) = material {
+                    instance = instance.with_material(lookup(materials, path, name)?);
+                }
+                Arc::new(instance)
+            }
+            ObjectSpec::ConstantMedium {
+                boundary,
+                density,
+                material,
+            } => {
+                let boundary =
+                    //a glowing boundary is not drawn, so it is no light either
+                    boundary.build(
+                        &format!("{}.boundary", path),
+                        materials,
+                        prototypes,
+                        base_dir,
+                        &mut Vec::new(),
+                    )?;
+                if !(*density > 0.0 && density.is_finite()) {
+                    return Err(invalid(format!("{}.density", path), "must be > 0"));
+                }
+                Arc::new(ConstantMedium::new(
+                    boundary,
+                    *density,
+                    lookup(materials, path, material)?,
+                ))
+            }
+            ObjectSpec::GridMedium {
+                path: file,
+                resolution,
+                min,
+                max,
+                density,
+                material,
+            } => {
+                check_finite(&format!("{}.min", path), min)?;
+                check_finite(&format!("{}.max", path), max)?;
+                if (0..3).any(|i| min[i] >= max[i]) {
+                    return Err(invalid(format!("{}.max", path), "must be above min"));
+                }
+                if !(*density >= 0.0 && density.is_finite()) {
+                    return Err(invalid(format!("{}.density", path), "must be >= 0"));
+                }
+                let phase_function = lookup(materials, path, material)?;
+                let file = base_dir.join(file);
+                let grid = DensityGrid::load_raw(&file, *resolution)
+                    .map_err(|source| SceneError::Io { file, source })?;
+                Arc::new(GridMedium::new(
+                    Aabb::new(vec3(*min), vec3(*max)),
+                    Arc::new(grid),
+                    *density,
+                    phase_function,
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::HitRecord;
+    use crate::ray::Ray;
+
+    fn build(source: &str) -> Result<Scene, SceneError> {
+        SceneFile::from_json(source)?.build(Path::new("."))
+    }
+
+    //a scene with the test camera and `body` after it
+    fn scene(body: &str) -> String {
+        format!("{{ {}, {} }}", CAMERA, body)
+    }
+
+    fn hit(scene: &Scene, origin: [f64; 3], direction: [f64; 3]) -> Option<HitRecord> {
+        let r = Ray::new(vec3(origin), vec3(direction), 0.0);
+        scene.world.hit(&r, 0.001, f64::INFINITY)
+    }
+
+    //albedo of the surface first hit by the ray, read off its BSDF along the normal
+    fn albedo(scene: &Scene, origin: [f64; 3], direction: [f64; 3]) -> Color {
+        let r = Ray::new(vec3(origin), vec3(direction), 0.0);
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        std::f64::consts::PI * rec.material.eval(&r, &rec, rec.normal)
+    }
+
+    const CAMERA: &str = r#""camera": { "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40, "aspect_ratio": 1.5 }"#;
+    const GREY: &str =
+        r#""materials": { "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } }"#;
+
+    #[test]
+    fn test_invalid_scenes() {
+        // whole scenes, and the path of the value they are rejected for
+        let cases = [
+            (
+                scene(
+                    r#""materials": { "red": { "type": "lambertian", "albedo": [1, 0, 0] } },
+                       "objects": [ { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "red" },
+                                    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "blue" } ]"#,
+                ),
+                "objects[1].material",
+            ),
+            (
+                scene(r#""materials": { "m": { "type": "metal", "albedo": [1, 1, 1], "fuzz": 2 } }"#),
+                "materials.m.fuzz",
+            ),
+            (
+                r#"{ "camera": { "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 200, "aspect_ratio": 1 } }"#
+                    .to_string(),
+                "camera.vfov",
+            ),
+            // tagged enums are buffered by serde, so the path stops at the material
+            (
+                scene(r#""materials": { "m": { "type": "lambertian", "albedo": "red" } }"#),
+                "materials.m",
+            ),
+            (scene(r#""render": { "image_widht": 10 }"#), "render.image_widht"),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "triangle", "vertices": [[0, 0, 0], [1e-4, 0, 0], [2e-4, 0, 0]],
+                                          "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].vertices",
+            ),
+            (
+                scene(r#""environment": { "type": "gradient", "top": [0, -1, 0] }"#),
+                "environment.top",
+            ),
+            (
+                r#"{ "camera": { "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40,
+                     "aspect_ratio": 1, "focus_dist": 2, "autofocus": true } }"#
+                    .to_string(),
+                "camera.focus_dist",
+            ),
+            (
+                r#"{ "camera": { "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40,
+                     "aspect_ratio": 1, "aperture": -1 } }"#
+                    .to_string(),
+                "camera.aperture",
+            ),
+            (
+                r#"{ "camera": { "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40,
+                     "aspect_ratio": 1, "shutter": [1, 0] } }"#
+                    .to_string(),
+                "camera.shutter",
+            ),
+            (
+                scene(
+                    r#""materials": { "floor": { "type": "lambertian", "albedo": {
+                        "type": "checker", "scale": 1, "even": [1, 1, 1],
+                        "odd": { "type": "checker", "scale": 0.1, "even": [0, 0, 0], "odd": [2, -1, 0] }
+                    } } }"#,
+                ),
+                "materials.floor.albedo.odd.odd",
+            ),
+            (
+                scene(
+                    r#""materials": { "smoke": { "type": "isotropic", "albedo": [0.5, 0.5, 0.5] } },
+                       "objects": [ { "type": "constant_medium", "density": 0.5, "material": "smoke",
+                         "boundary": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "fog" } } ]"#,
+                ),
+                "objects[0].boundary.material",
+            ),
+            (
+                scene(
+                    r#""materials": { "smoke": { "type": "henyey_greenstein", "albedo": [0.8, 0.8, 0.8], "g": 0.4 } },
+                       "objects": [ { "type": "grid_medium", "path": "missing.raw", "resolution": [2, 2, 2],
+                         "min": [0, 0, 0], "max": [1, 0, 1], "material": "smoke" } ]"#,
+                ),
+                "objects[0].max",
+            ),
+            (
+                scene(r#""materials": { "smoke": { "type": "henyey_greenstein", "albedo": [0.8, 0.8, 0.8], "g": 1 } }"#),
+                "materials.smoke.g",
+            ),
+            (
+                scene(
+                    r#""materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+                       "objects": [ { "type": "quad", "q": [0, 0, 0], "u": [1, 0, 0], "v": [2, 0, 0], "material": "lamp" } ]"#,
+                ),
+                "objects[0].v",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0],
+                                          "tile_size": 0, "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].tile_size",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "cone", "base": [3, 0, 0], "radius": 1, "height": 2,
+                                          "phi_max": 400, "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].phi_max",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "ellipsoid", "center": [0, 3, 0], "radii": [2, 0, 1],
+                                          "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].radii",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "paraboloid", "base": [-3, 0, 0], "radius": 1, "height": -1,
+                                          "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].height",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "objects": [ {{ "type": "torus", "center": [0, 1, 0], "major_radius": 2,
+                                          "minor_radius": 3, "material": "grey" }} ]"#,
+                    GREY
+                )),
+                "objects[0].minor_radius",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "prototypes": {{ "ball": {{ "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "grey" }} }},
+                       "objects": [ {{ "type": "instance", "of": "cube" }} ]"#,
+                    GREY
+                )),
+                "objects[0].of",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "prototypes": {{ "ball": {{ "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "grey" }} }},
+                       "objects": [ {{ "type": "instance", "of": "ball", "transform": [ {{ "scale": [2, 0, 1] }} ] }} ]"#,
+                    GREY
+                )),
+                "objects[0].transform[0].scale",
+            ),
+            (
+                scene(&format!(
+                    r#"{}, "prototypes": {{ "ball": {{ "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "grey" }} }},
+                       "objects": [ {{ "type": "instance", "of": "ball",
+                                       "transform": [ {{ "translate": [1, 0, 0] }}, {{ "rotate": {{ "axis": [0, 0, 0], "degrees": 90 }} }} ] }} ]"#,
+                    GREY
+                )),
+                "objects[0].transform[1].rotate.axis",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            match build(source) {
+                Err(SceneError::Invalid { path, .. }) => assert_eq!(&path, expected),
+                Err(e) => panic!("{}: unexpected error {}", expected, e),
+                Ok(_) => panic!("{}: expected an error", expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let cases = [
+            scene(r#""environment": { "type": "image", "path": "missing.hdr" }"#),
+            scene(
+                r#""materials": { "m": { "type": "metal", "fuzz": 0,
+                     "albedo": { "type": "image", "path": "missing.png", "wrap": "mirror" } } }"#,
+            ),
+            scene(
+                r#""materials": { "smoke": { "type": "henyey_greenstein", "albedo": [0.8, 0.8, 0.8], "g": 0.4 } },
+                   "objects": [ { "type": "grid_medium", "path": "missing.raw", "resolution": [2, 2, 2],
+                     "min": [0, 0, 0], "max": [1, 1, 1], "material": "smoke" } ]"#,
+            ),
+        ];
+        for source in cases.iter() {
+            assert!(
+                matches!(build(source), Err(SceneError::Io { .. })),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_scenes_build() {
+        for name in SceneFile::builtin_names() {
+            let scene = SceneFile::builtin(name).unwrap();
+            assert!(scene.build(Path::new(".")).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_triangle() {
+        // sides of 1e-4 are small, not degenerate
+        let source = scene(&format!(
+            r#"{}, "objects": [ {{ "type": "triangle", "vertices": [[0, 0, 0], [1e-4, 0, 0], [0, 1e-4, 0]],
+                                  "material": "grey" }} ]"#,
+            GREY
+        ));
+        let scene = build(&source).unwrap();
+        let rec = hit(&scene, [2e-5, 2e-5, 1.0], [0.0, 0.0, -1.0]).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12 && rec.front_face);
+        assert!(hit(&scene, [2e-4, 2e-5, 1.0], [0.0, 0.0, -1.0]).is_none());
+    }
+
+    #[test]
+    fn test_image_height() {
+        let scene = build(&scene(r#""render": { "image_width": 300 }"#)).unwrap();
+        assert_eq!(scene.image_height, 200);
+        assert_eq!(scene.settings.samples_per_pixel, 1000);
+    }
+
+    #[test]
+    fn test_environment() {
+        let scene = build(&scene(
+            r#""environment": { "type": "solid", "color": [0, 0, 0] }"#,
+        ))
+        .unwrap();
+        let up = scene.environment.radiance(Vec3::new(0.0, 1.0, 0.0));
+        assert!(up.length() == 0.0);
+    }
+
+    #[test]
+    fn test_lights() {
+        let source = scene(
+            r#""materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] },
+                              "off": { "type": "diffuse_light", "emit": [0, 0, 0] },
+                              "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+               "objects": [ { "type": "sphere", "center": [0, 0, -1], "radius": 0.5, "material": "lamp" },
+                            { "type": "quad", "q": [0, 0, 0], "u": [1, 0, 0], "v": [0, 1, 0], "material": "lamp" },
+                            { "type": "quad", "q": [0, 0, 0], "u": [1, 0, 0], "v": [0, 0, 1], "material": "off" },
+                            { "type": "box", "min": [2, 0, 0], "max": [3, 1, 1], "material": "lamp" },
+                            { "type": "disk", "center": [0, 3, 0], "normal": [0, -1, 0], "radius": 1, "material": "lamp" },
+                            { "type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0], "tile_size": 2, "material": "grey" },
+                            { "type": "sphere", "center": [0, -100, -1], "radius": 99, "material": "grey" } ]"#,
+        );
+        assert_eq!(build(&source).unwrap().lights.len(), 9);
+
+        // a lamp 1e-4 across is small, not degenerate
+        let source = scene(
+            r#""materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+               "objects": [ { "type": "quad", "q": [0, 0, 0], "u": [1e-4, 0, 0], "v": [0, 1e-4, 0], "material": "lamp" } ]"#,
+        );
+        let scene = build(&source).unwrap();
+        assert_eq!(scene.lights.len(), 1);
+        assert!(hit(&scene, [5e-5, 5e-5, 1.0], [0.0, 0.0, -1.0]).is_some());
+    }
+
+    #[test]
+    fn test_quadrics() {
+        let source = scene(&format!(
+            r#"{}, "objects": [
+                 {{ "type": "cylinder", "base": [0, 0, 0], "radius": 1, "height": 2, "capped": true, "material": "grey" }},
+                 {{ "type": "cone", "base": [3, 0, 0], "radius": 1, "height": 2, "phi_max": 270, "material": "grey" }},
+                 {{ "type": "ellipsoid", "center": [0, 3, 0], "radii": [2, 1, 1], "material": "grey" }},
+                 {{ "type": "paraboloid", "base": [-3, 0, 0], "radius": 1, "height": 1, "material": "grey" }}
+               ]"#,
+            GREY
+        ));
+        let scene = build(&source).unwrap();
+        let bbox = scene.world.bounding_box().unwrap();
+        assert!((bbox.min().x() + 4.0).abs() < 1e-12 && (bbox.max().y() - 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_torus() {
+        let source = scene(&format!(
+            r#"{}, "objects": [
+                 {{ "type": "torus", "center": [0, 1, 0], "major_radius": 2, "minor_radius": 0.5, "material": "grey" }}
+               ]"#,
+            GREY
+        ));
+        let scene = build(&source).unwrap();
+        let bbox = scene.world.bounding_box().unwrap();
+        assert!((bbox.max().x() - 2.5).abs() < 1e-12 && (bbox.max().y() - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_instances() {
+        let source = scene(
+            r#""materials": { "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] },
+                              "red": { "type": "lambertian", "albedo": [1, 0, 0] } },
+               "prototypes": { "ball": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "grey" } },
+               "objects": [
+                 { "type": "instance", "of": "ball" },
+                 { "type": "instance", "of": "ball", "material": "red",
+                   "transform": [ { "scale": [2, 1, 1] }, { "rotate": { "axis": [0, 1, 0], "degrees": 90 } },
+                                  { "translate": [0, 0, -10] } ] }
+               ]"#,
+        );
+        let scene = build(&source).unwrap();
+        // stretched along x, then turned to lie along z
+        let bbox = scene.world.bounding_box().unwrap();
+        assert!((bbox.min().z() + 12.0).abs() < 1e-9 && (bbox.max().x() - 1.0).abs() < 1e-9);
+        assert!(scene.lights.is_empty());
+    }
+
+    #[test]
+    fn test_diffuse_light() {
+        let source = scene(
+            r#""materials": { "lamp": { "type": "diffuse_light", "emit": [4, 2, 1] } },
+               "objects": [ { "type": "sphere", "center": [0, 0, -2], "radius": 1, "material": "lamp" } ]"#,
+        );
+        let scene = build(&source).unwrap();
+        let rec = hit(&scene, [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12 && rec.material.is_emissive());
+        let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+        assert!((emitted - Color::new(4.0, 2.0, 1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_autofocus() {
+        // the sphere's front is 4 away, so every lens sample of the image
+        // center passes through it
+        let source = format!(
+            r#"{{ "camera": {{ "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40, "aspect_ratio": 1,
+                             "aperture": 0.5, "autofocus": true }}, {},
+                 "objects": [ {{ "type": "sphere", "center": [0, 0, -4], "radius": 1, "material": "grey" }} ] }}"#,
+            GREY
+        );
+        let scene = build(&source).unwrap();
+        for _ in 0..10 {
+            let r = scene.camera.get_ray(0.5, 0.5);
+            let p = r.at((-3.0 - r.origin().z()) / r.direction().z());
+            assert!(p.x().abs() < 1e-9 && p.y().abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        // the shutter stays open after the sphere has stopped at x = 4
+        let source = format!(
+            r#"{{ "camera": {{ "lookfrom": [0, 0, 1], "lookat": [0, 0, 0], "vfov": 40, "aspect_ratio": 1,
+                             "shutter": [0, 2] }}, {},
+                 "objects": [ {{ "type": "moving_sphere", "center0": [0, 0, -3], "center1": [4, 0, -3],
+                                "radius": 1, "material": "grey" }},
+                              {{ "type": "sphere", "center": [-10, 0, -3], "radius": 1, "material": "grey" }} ] }}"#,
+            GREY
+        );
+        let scene = build(&source).unwrap();
+        let at = |x: f64, time: f64| {
+            let r = Ray::new(vec3([x, 0.0, 1.0]), vec3([0.0, 0.0, -1.0]), time);
+            scene.world.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t)
+        };
+        assert!((at(2.0, 0.5).unwrap() - 3.0).abs() < 1e-9);
+        assert!(at(2.0, 1.5).is_none());
+        assert!((at(4.0, 1.5).unwrap() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_checker() {
+        // unit cells on a floor at y = -0.5, so x in (0, 1) is even and (1, 2) odd
+        let source = scene(
+            r#""materials": { "floor": { "type": "lambertian", "albedo": {
+                   "type": "checker", "scale": 1, "even": [1, 1, 1], "odd": [0.2, 0.4, 0.6] } } },
+               "objects": [ { "type": "quad", "q": [-10, -0.5, 10], "u": [20, 0, 0], "v": [0, 0, -20],
+                              "material": "floor" } ]"#,
+        );
+        let scene = build(&source).unwrap();
+        let even = albedo(&scene, [0.5, 1.0, -0.5], [0.0, -1.0, 0.0]);
+        let odd = albedo(&scene, [1.5, 1.0, -0.5], [0.0, -1.0, 0.0]);
+        assert!((even - Color::new(1.0, 1.0, 1.0)).length() < 1e-9);
+        assert!((odd - Color::new(0.2, 0.4, 0.6)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_noise() {
+        // the floor carries the pattern of its seed
+        let source = scene(
+            r#""materials": { "floor": { "type": "lambertian", "albedo": { "type": "noise", "scale": 4, "seed": 7 } } },
+               "objects": [ { "type": "quad", "q": [-10, -0.5, 10], "u": [20, 0, 0], "v": [0, 0, -20],
+                              "material": "floor" } ]"#,
+        );
+        let scene = build(&source).unwrap();
+        let noise = NoiseTexture::new(7, 4.0);
+        for x in [0.1, 0.7, 1.3] {
+            let expected = noise.value(0.0, 0.0, &vec3([x, -0.5, -0.5]));
+            assert!((albedo(&scene, [x, 1.0, -0.5], [0.0, -1.0, 0.0]) - expected).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_constant_medium() {
+        // a fog ball 2 across at density 0.5 lets exp(-1) through its middle
+        let source = scene(
+            r#""materials": { "smoke": { "type": "isotropic", "albedo": [0.5, 0.5, 0.5] } },
+               "objects": [ { "type": "constant_medium", "density": 0.5, "material": "smoke",
+                 "boundary": { "type": "sphere", "center": [0, 0, -3], "radius": 1, "material": "smoke" } } ]"#,
+        );
+        let scene = build(&source).unwrap();
+        let through = |x: f64| {
+            let r = Ray::new(vec3([x, 0.0, 1.0]), vec3([0.0, 0.0, -1.0]), 0.0);
+            scene.world.transmittance(&r, 0.001, f64::INFINITY)
+        };
+        assert!((through(0.0) - (-1.0f64).exp()).abs() < 1e-9);
+        assert_eq!(through(2.0), 1.0);
+    }
+
+    #[test]
+    fn test_grid_medium() {
+        // an empty 2 x 2 x 2 grid, found next to the scene file
+        let dir = std::env::temp_dir();
+        let bytes: Vec<u8> = [0.0f32; 8].iter().flat_map(|d| d.to_le_bytes()).collect();
+        fs::write(dir.join("scene_grid_test.raw"), bytes).unwrap();
+        let source = scene(
+            r#""materials": { "smoke": { "type": "henyey_greenstein", "albedo": [0.8, 0.8, 0.8], "g": 0.4 } },
+               "objects": [ { "type": "grid_medium", "path": "scene_grid_test.raw", "resolution": [2, 2, 2],
+                 "min": [-1, -1, -4], "max": [1, 2, -2], "density": 3, "material": "smoke" } ]"#,
+        );
+        let scene = SceneFile::from_json(&source).unwrap().build(&dir).unwrap();
+        fs::remove_file(dir.join("scene_grid_test.raw")).unwrap();
+
+        let bbox = scene.world.bounding_box().unwrap();
+        assert!((bbox.min() - vec3([-1.0, -1.0, -4.0])).length() < 1e-12);
+        assert!((bbox.max() - vec3([1.0, 2.0, -2.0])).length() < 1e-12);
+        let r = Ray::new(vec3([0.0, 0.0, 1.0]), vec3([0.0, 0.0, -1.0]), 0.0);
+        assert_eq!(scene.world.transmittance(&r, 0.001, f64::INFINITY), 1.0);
+        assert!(scene.world.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_box() {
+        let source = scene(&format!(
+            r#"{}, "objects": [ {{ "type": "box", "min": [-1, -1, -4], "max": [1, 2, -2], "material": "grey" }} ]"#,
+            GREY
+        ));
+        let scene = build(&source).unwrap();
+        // the front face from outside, the back face from inside
+        let rec = hit(&scene, [0.5, 1.5, 1.0], [0.0, 0.0, -1.0]).unwrap();
+        assert!(
+            (rec.t - 3.0).abs() < 1e-12 && rec.front_face && (rec.normal.z() - 1.0).abs() < 1e-12
+        );
+        let rec = hit(&scene, [0.0, 0.0, -3.0], [0.0, 1.0, 0.0]).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12 && !rec.front_face);
+        assert!(hit(&scene, [1.5, 0.0, 1.0], [0.0, 0.0, -1.0]).is_none());
+    }
+
+    #[test]
+    fn test_plane_and_disk() {
+        // a floor at y = -1 under an annulus at y = 3 with a hole of radius 0.5
+        let source = scene(&format!(
+            r#"{}, "objects": [
+                 {{ "type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0], "tile_size": 2, "material": "grey" }},
+                 {{ "type": "disk", "center": [0, 3, 0], "normal": [0, -1, 0], "radius": 1, "inner_radius": 0.5,
+                    "material": "grey" }}
+               ]"#,
+            GREY
+        ));
+        let scene = build(&source).unwrap();
+        let rec = hit(&scene, [100.0, 5.0, -40.0], [0.0, -1.0, 0.0]).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-12 && (rec.normal.y() - 1.0).abs() < 1e-12);
+        let rec = hit(&scene, [0.75, 0.0, 0.0], [0.0, 1.0, 0.0]).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12 && rec.front_face);
+        // through the hole and out past the rim
+        assert!(hit(&scene, [0.25, 0.0, 0.0], [0.0, 1.0, 0.0]).is_none());
+        assert!(hit(&scene, [1.25, 0.0, 0.0], [0.0, 1.0, 0.0]).is_none());
+    }
+}
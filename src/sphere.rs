@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use crate::material::Scatter;
 
+use super::aabb::Aabb;
 use super::hit::{Hit, HitRecord};
+use super::light::Light;
+use super::random::random_double;
 use super::ray::Ray;
 use super::vec::{Point3, Vec3};
 
@@ -20,56 +23,287 @@ impl Sphere {
             material: m,
         }
     }
+
+    //p: point on the unit sphere
+    //u: angle around the Y axis from X=-1, v: angle from Y=-1 to Y=+1, both in [0,1]
+    fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
 }
 
 //(P−C)⋅(P−C)=r2
 //P(t)=A+tb, A is ray source
 //t^2 b⋅b+t 2b⋅(A−C)+(A−C)⋅(A−C)−r^2=0
+fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    material: &Arc<dyn Scatter>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let oc = ray.origin() - center;
+    let a = ray.direction().length().powi(2);
+    let half_b = ray.direction().dot(oc);
+    let c = oc.length().powi(2) - radius.powi(2);
+    let discriminant = half_b.powi(2) - a * c;
+
+    //2 root
+    //2正，取符合范围小的
+    //1正1负，在内部，取正的
+    //2负，在背后，忽略
+
+    let sqrtd = discriminant.sqrt();
+    //较小的
+    let root = (-half_b - sqrtd) / a;
+    if root < t_max && root > t_min {
+        let mut rec = HitRecord {
+            p: ray.at(root),
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t: root,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: false,
+            material: material.clone(),
+        };
+        (rec.u, rec.v) = Sphere::get_sphere_uv((rec.p - center).normalized());
+        rec.set_face_normal(ray, (rec.p - center).normalized());
+        return Some(rec);
+    }
+    //较大的
+    let root = (-half_b + sqrtd) / a;
+    if root < t_max && root > t_min {
+        let mut rec = HitRecord {
+            p: ray.at(root),
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t: root,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            front_face: false,
+            material: material.clone(),
+        };
+        (rec.u, rec.v) = Sphere::get_sphere_uv((rec.p - center).normalized());
+        rec.set_face_normal(ray, ((rec.p - center) / radius).normalized());
+        return Some(rec);
+    }
+    //0 root，不相交
+    //1 root，相切，忽略
+
+    None
+
+    //
+}
+
+fn sphere_box(center: Point3, radius: f64) -> Aabb {
+    //radius may be negative for hollow glass
+    let r = Vec3::new(radius, radius, radius);
+    Aabb::new(center - r, center + r)
+}
+
 impl Hit for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin() - self.center;
-        let a = ray.direction().length().powi(2);
-        let half_b = ray.direction().dot(oc);
-        let c = oc.length().powi(2) - self.radius.powi(2);
-        let discriminant = half_b.powi(2) - a * c;
-
-        //2 root
-        //2正，取符合范围小的
-        //1正1负，在内部，取正的
-        //2负，在背后，忽略
-
-        let sqrtd = discriminant.sqrt();
-        //较小的
-        let root = (-half_b - sqrtd) / a;
-        if root < t_max && root > t_min {
-            let mut rec = HitRecord {
-                p: ray.at(root),
-                normal: Vec3::new(0.0, 0.0, 0.0),
-                t: root,
-                front_face: false,
-                material: self.material.clone(),
-            };
-            rec.set_face_normal(ray, (rec.p - self.center).normalized());
-            return Some(rec);
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(sphere_box(self.center, self.radius))
+    }
+}
+
+impl Sphere {
+    //cosine of the half angle the sphere covers seen from outside at `origin`
+    fn cos_theta_max(&self, origin: Point3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length().powi(2);
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
         }
-        //较大的
-        let root = (-half_b + sqrtd) / a;
-        if root < t_max && root > t_min {
-            let mut rec = HitRecord {
-                p: ray.at(root),
-                normal: Vec3::new(0.0, 0.0, 0.0),
-                t: root,
-                front_face: false,
-                material: self.material.clone(),
-            };
-            rec.set_face_normal(ray, ((rec.p - self.center) / self.radius).normalized());
-            return Some(rec);
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+//uniform over the cone of directions that see the sphere; from inside
+//there is no cone and the light is left to BSDF sampling
+impl Light for Sphere {
+    fn sample_light(&self, origin: Point3) -> Option<(Vec3, f64)> {
+        let cos_theta_max = self.cos_theta_max(origin)?;
+        let cos_theta = 1.0 + random_double() * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * random_double();
+
+        let w = (self.center - origin).normalized();
+        let (u, v) = w.orthonormal_basis();
+        let direction = sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+        Some((
+            direction,
+            1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_theta_max)),
+        ))
+    }
+
+    fn pdf(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return 0.0;
+        };
+        if self
+            .hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
         }
-        //0 root，不相交
-        //1 root，相切，忽略
+        1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_theta_max))
+    }
+}
 
-        None
+/// Moves linearly from `center0` at `time0` to `center1` at `time1`,
+/// and rests at either end outside that interval so it never leaves its
+/// bounding box.
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Scatter>,
+}
 
-        //
+impl MovingSphere {
+    pub fn new(
+        center0: Point3,
+        center1: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Scatter>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let s = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + s * (self.center1 - self.center0)
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(ray.time());
+        hit_sphere(center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    //covers the whole motion between time0 and time1
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::surrounding(
+            sphere_box(self.center0, self.radius),
+            sphere_box(self.center1, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::hit::World;
+    use crate::material::Lambertian;
+    use crate::vec::Color;
+
+    #[test]
+    fn test_moving_sphere() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = MovingSphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(4.0, 0.0, 0.0),
+            0.0,
+            1.0,
+            1.0,
+            material,
+        );
+        let origin = Point3::new(4.0, 0.0, 5.0);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        assert!(sphere
+            .hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY)
+            .is_none());
+        let rec = sphere
+            .hit(&Ray::new(origin, direction, 1.0), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!((sphere.center(0.25).x() - 1.0).abs() < 1e-12);
+
+        let bbox = sphere.bounding_box().unwrap();
+        assert!((bbox.min().x() - -1.0).abs() < 1e-12);
+        assert!((bbox.max().x() - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_shutter_past_the_motion() {
+        // the shutter stays open until 2, after the sphere stopped at x = 4
+        let material: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let world: World = vec![
+            Arc::new(MovingSphere::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(4.0, 0.0, 0.0),
+                0.0,
+                1.0,
+                1.0,
+                material.clone(),
+            )),
+            Arc::new(Sphere::new(Point3::new(-10.0, 0.0, 0.0), 1.0, material)),
+        ];
+        let bvh = BvhNode::new(world);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        let rec = bvh
+            .hit(
+                &Ray::new(Point3::new(4.0, 0.0, 5.0), direction, 2.0),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!(bvh
+            .hit(
+                &Ray::new(Point3::new(8.0, 0.0, 5.0), direction, 2.0),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -4.0), 1.0, material);
+        let origin = Point3::new(0.0, 1.0, 0.0);
+        for _ in 0..100 {
+            let (direction, pdf) = sphere.sample_light(origin).unwrap();
+            assert!((pdf - sphere.pdf(origin, direction)).abs() < 1e-9 * pdf);
+        }
+        // integrates to one over all directions
+        let n = 200000;
+        let total: f64 = (0..n)
+            .map(|_| sphere.pdf(origin, Vec3::random_unit_vector()))
+            .sum::<f64>()
+            * 4.0
+            * std::f64::consts::PI
+            / n as f64;
+        assert!((total - 1.0).abs() < 0.05);
+        // no cone from inside
+        assert!(sphere.sample_light(Point3::new(0.0, 0.0, -4.5)).is_none());
     }
 }
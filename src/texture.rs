@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use super::input::{Image, Wrap};
+use super::perlin::Perlin;
+use super::vec::{Color, Point3};
+
+/// Color that varies over a surface, looked up by the hit's (u, v) and point.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+/// Alternating cubes of edge `scale` in space, so it needs no UVs.
+pub struct CheckerTexture {
+    scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> CheckerTexture {
+        CheckerTexture { scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let cell = |x: f64| (x / self.scale).floor() as i64;
+        if (cell(p.x()) + cell(p.y()) + cell(p.z())).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Bilinear lookup in an image, (0, 0) is the bottom left corner.
+pub struct ImageTexture {
+    image: Image,
+    wrap_u: Wrap,
+    wrap_v: Wrap,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image, wrap_u: Wrap, wrap_v: Wrap) -> ImageTexture {
+        ImageTexture {
+            image,
+            wrap_u,
+            wrap_v,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        self.image.bilinear(u, v, self.wrap_u, self.wrap_v)
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+/// Plain smooth noise in gray, `scale` is the frequency.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f64) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let n = self.noise.noise(&(self.scale * *p));
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + n)
+    }
+}
+
+/// Veins along z, a sine wave phase-shifted by turbulence.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    base: Color,
+    vein: Color,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64, scale: f64, base: Color, vein: Color) -> MarbleTexture {
+        MarbleTexture {
+            noise: Perlin::new(seed),
+            scale,
+            base,
+            vein,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let phase = self.scale * p.z() + 10.0 * self.noise.turb(p, 7);
+        lerp(self.vein, self.base, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+/// Growth rings around the y axis, `scale` rings per unit, wobbled by noise.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64, scale: f64, light: Color, dark: Color) -> WoodTexture {
+        WoodTexture {
+            noise: Perlin::new(seed),
+            scale,
+            light,
+            dark,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = self.scale * radius + 0.5 * self.noise.noise(&(4.0 * *p));
+        //sharp edge at the end of each ring
+        let t = rings.rem_euclid(1.0).powi(3);
+        lerp(self.light, self.dark, t)
+    }
+}
+
+/// Fractal Brownian motion between `sky` and `cloud`.
+pub struct CloudTexture {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+    sky: Color,
+    cloud: Color,
+}
+
+impl CloudTexture {
+    pub fn new(seed: u64, scale: f64, octaves: u32, sky: Color, cloud: Color) -> CloudTexture {
+        CloudTexture {
+            noise: Perlin::new(seed),
+            scale,
+            octaves,
+            sky,
+            cloud,
+        }
+    }
+}
+
+impl Texture for CloudTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let n = self.noise.fbm(&(self.scale * *p), self.octaves);
+        lerp(self.sky, self.cloud, (0.5 + n).clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker() {
+        let black = Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+        let white = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+        let checker = CheckerTexture::new(0.5, black, white);
+        let at = |x, y, z| checker.value(0.0, 0.0, &Point3::new(x, y, z)).x();
+        assert_eq!(at(0.1, 0.1, 0.1), 0.0);
+        assert_eq!(at(0.6, 0.1, 0.1), 1.0);
+        assert_eq!(at(0.6, 0.6, 0.1), 0.0);
+        // no seam at the origin
+        assert_eq!(at(-0.1, 0.1, 0.1), 1.0);
+    }
+
+    #[test]
+    fn test_procedural_textures_stay_between_their_colors() {
+        let a = Color::new(0.1, 0.2, 0.3);
+        let b = Color::new(0.9, 0.8, 0.7);
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(NoiseTexture::new(3, 4.0)),
+            Box::new(MarbleTexture::new(3, 4.0, a, b)),
+            Box::new(WoodTexture::new(3, 4.0, a, b)),
+            Box::new(CloudTexture::new(3, 4.0, 6, a, b)),
+        ];
+        for texture in textures.iter() {
+            for i in 0..200 {
+                let x = i as f64 * 0.0731;
+                let c = texture.value(0.0, 0.0, &Point3::new(x, 1.0 - x, 0.5 * x));
+                assert!(c.x() >= 0.0 && c.x() <= 1.0);
+                assert!(c.z() >= 0.0 && c.z() <= 1.0);
+            }
+        }
+        // same seed, same pattern
+        let p = Point3::new(0.3, 0.4, 0.5);
+        let c0 = MarbleTexture::new(9, 4.0, a, b).value(0.0, 0.0, &p);
+        let c1 = MarbleTexture::new(9, 4.0, a, b).value(0.0, 0.0, &p);
+        assert_eq!(c0.y(), c1.y());
+    }
+
+    #[test]
+    fn test_image_texture() {
+        let image = Image {
+            width: 1,
+            height: 2,
+            pixels: vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0)],
+        };
+        let texture = ImageTexture::new(image, Wrap::Repeat, Wrap::Clamp);
+        let p = Point3::new(0.0, 0.0, 0.0);
+        // v goes up, the first row is the top
+        assert_eq!(texture.value(0.5, 0.75, &p).x(), 1.0);
+        assert_eq!(texture.value(0.5, 0.25, &p).z(), 1.0);
+        assert_eq!(texture.value(0.5, 1.5, &p).x(), 1.0);
+    }
+}
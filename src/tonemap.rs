@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use super::vec::Color;
+
+/// Maps linear scene radiance to linear display values in [0, 1].
+pub trait ToneMapper: Send + Sync {
+    fn tone_map(&self, c: Color) -> Color;
+}
+
+pub const TONE_MAPPERS: [&str; 5] = ["clamp", "reinhard", "extended-reinhard", "aces", "hable"];
+
+/// `white` is the radiance mapped to 1.0 by extended Reinhard and Hable.
+pub fn tone_mapper_by_name(name: &str, white: f64) -> Option<Arc<dyn ToneMapper>> {
+    Some(match name {
+        "clamp" => Arc::new(Clamp),
+        "reinhard" => Arc::new(Reinhard),
+        "extended-reinhard" => Arc::new(ExtendedReinhard { white }),
+        "aces" => Arc::new(Aces),
+        "hable" => Arc::new(Hable { white }),
+        _ => return None,
+    })
+}
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+//scale the color so its luminance becomes l_out, keeps the hue
+fn with_luminance(c: Color, l_in: f64, l_out: f64) -> Color {
+    if l_in <= 0.0 {
+        Color::new(0.0, 0.0, 0.0)
+    } else {
+        c * (l_out / l_in)
+    }
+}
+
+fn per_channel(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+/// No curve, values above 1 clip.
+pub struct Clamp;
+
+impl ToneMapper for Clamp {
+    fn tone_map(&self, c: Color) -> Color {
+        c
+    }
+}
+
+/// L / (1 + L) on luminance.
+pub struct Reinhard;
+
+impl ToneMapper for Reinhard {
+    fn tone_map(&self, c: Color) -> Color {
+        let l = luminance(c);
+        with_luminance(c, l, l / (1.0 + l))
+    }
+}
+
+/// L (1 + L / white^2) / (1 + L), reaches 1 at `white` instead of infinity.
+pub struct ExtendedReinhard {
+    pub white: f64,
+}
+
+impl ToneMapper for ExtendedReinhard {
+    fn tone_map(&self, c: Color) -> Color {
+        let l = luminance(c);
+        let l_out = l * (1.0 + l / (self.white * self.white)) / (1.0 + l);
+        with_luminance(c, l, l_out)
+    }
+}
+
+/// Narkowicz' fit of the ACES filmic curve.
+pub struct Aces;
+
+impl ToneMapper for Aces {
+    fn tone_map(&self, c: Color) -> Color {
+        const A: f64 = 2.51;
+        const B: f64 = 0.03;
+        const C: f64 = 2.43;
+        const D: f64 = 0.59;
+        const E: f64 = 0.14;
+        per_channel(c, |x| {
+            let x = x.max(0.0);
+            (x * (A * x + B)) / (x * (C * x + D) + E)
+        })
+    }
+}
+
+/// John Hable's Uncharted 2 filmic curve, normalized so `white` maps to 1.
+pub struct Hable {
+    pub white: f64,
+}
+
+impl Hable {
+    fn curve(x: f64) -> f64 {
+        const A: f64 = 0.15; // shoulder strength
+        const B: f64 = 0.50; // linear strength
+        const C: f64 = 0.10; // linear angle
+        const D: f64 = 0.20; // toe strength
+        const E: f64 = 0.02; // toe numerator
+        const F: f64 = 0.30; // toe denominator
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+}
+
+impl ToneMapper for Hable {
+    fn tone_map(&self, c: Color) -> Color {
+        //the usual exposure bias of the reference implementation
+        const EXPOSURE_BIAS: f64 = 2.0;
+        let scale = 1.0 / Self::curve(EXPOSURE_BIAS * self.white);
+        per_channel(c, |x| Self::curve(EXPOSURE_BIAS * x.max(0.0)) * scale)
+    }
+}
+
+/// Piecewise sRGB transfer function, linear in [0, 1] to encoded in [0, 1].
+pub fn srgb_encode(x: f64) -> f64 {
+    let x = x.clamp(0.0, 1.0);
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_encode`, for 8-bit textures.
+pub fn srgb_decode(x: f64) -> f64 {
+    let x = x.clamp(0.0, 1.0);
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Exposure, tone curve and sRGB encoding, in that order.
+#[derive(Clone)]
+pub struct DisplayTransform {
+    //in stops, each one doubles the radiance
+    pub exposure: f64,
+    pub tone_mapper: Arc<dyn ToneMapper>,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        DisplayTransform {
+            exposure: 0.0,
+            tone_mapper: Arc::new(Clamp),
+        }
+    }
+}
+
+impl DisplayTransform {
+    /// Linear radiance to sRGB-encoded values in [0, 1].
+    pub fn apply(&self, c: Color) -> Color {
+        let exposed = c * 2f64.powf(self.exposure);
+        per_channel(self.tone_mapper.tone_map(exposed), srgb_encode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_encode() {
+        assert!((srgb_encode(0.0) - 0.0).abs() < 1e-12);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_encode(0.002) - 0.02584).abs() < 1e-12);
+        // both pieces meet at the threshold
+        let below = 12.92 * 0.0031308;
+        let above = 1.055 * 0.0031308f64.powf(1.0 / 2.4) - 0.055;
+        assert!((below - above).abs() < 1e-6);
+        assert!((srgb_encode(0.5) - 0.735357).abs() < 1e-6);
+        for x in [0.0, 0.001, 0.2, 0.5, 1.0] {
+            assert!((srgb_decode(srgb_encode(x)) - x).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_curves_are_monotonic_and_bounded() {
+        for name in TONE_MAPPERS {
+            let tm = tone_mapper_by_name(name, 4.0).unwrap();
+            let mut last = -1.0;
+            for i in 0..100 {
+                let x = i as f64 * 0.04;
+                let y = tm.tone_map(Color::new(x, x, x)).x();
+                assert!(y >= last, "{} not monotonic at {}", name, x);
+                last = y;
+            }
+            let y = tm.tone_map(Color::new(4.0, 4.0, 4.0)).x();
+            if name != "clamp" {
+                assert!(y <= 1.0 + 1e-9, "{} exceeds 1 at the white point", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_white_point() {
+        let white = Color::new(4.0, 4.0, 4.0);
+        let reinhard = ExtendedReinhard { white: 4.0 }.tone_map(white);
+        assert!((reinhard.y() - 1.0).abs() < 1e-9);
+        let hable = Hable { white: 4.0 }.tone_map(white);
+        assert!((hable.y() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let transform = DisplayTransform {
+            exposure: 1.0,
+            ..Default::default()
+        };
+        let c = transform.apply(Color::new(0.25, 0.0, 1.0));
+        assert!((c.x() - srgb_encode(0.5)).abs() < 1e-12);
+        assert!((c.z() - 1.0).abs() < 1e-12);
+    }
+}
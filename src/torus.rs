@@ -0,0 +1,158 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::poly::{solve_quadratic, solve_quartic};
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Ring around `center` in the y = 0 plane: a tube of `minor_radius`
+/// swept along a circle of `major_radius`. u goes around the ring from +x
+/// towards +z, v around the tube starting on the outside.
+pub struct Torus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Scatter>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64, m: Arc<dyn Scatter>) -> Torus {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material: m,
+        }
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        //solve along the unit direction from where the ray enters the
+        //bounding sphere; far origins otherwise cost the quartic its digits
+        let length = r.direction().length();
+        let d = r.direction() / length;
+        let o = r.origin() - self.center;
+        let bound = self.major_radius + self.minor_radius;
+        let (enter, exit) = solve_quadratic(1.0, 2.0 * o.dot(d), o.dot(o) - bound * bound)?;
+        if exit <= t_min * length || enter >= t_max * length {
+            return None;
+        }
+        let shift = enter.max(0.0);
+        let o = o + shift * d;
+
+        //(|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along p = o + s d
+        let r2 = self.major_radius * self.major_radius;
+        let n = o.dot(d);
+        let k = o.dot(o) + r2 - self.minor_radius * self.minor_radius;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * n,
+            4.0 * n * n + 2.0 * k - 4.0 * r2 * (d.x() * d.x() + d.z() * d.z()),
+            4.0 * n * k - 8.0 * r2 * (o.x() * d.x() + o.z() * d.z()),
+            k * k - 4.0 * r2 * (o.x() * o.x() + o.z() * o.z()),
+        );
+        let t = roots
+            .into_iter()
+            .map(|s| (s + shift) / length)
+            .find(|&t| t > t_min && t < t_max)?;
+
+        let p = r.at(t);
+        let local = p - self.center;
+        //outward from the nearest point of the center circle
+        let ring = Vec3::new(local.x(), 0.0, local.z());
+        let rho = ring.length();
+        let outward = local - self.major_radius / rho * ring;
+        let phi = local.z().atan2(local.x()).rem_euclid(2.0 * PI);
+        let theta = local
+            .y()
+            .atan2(rho - self.major_radius)
+            .rem_euclid(2.0 * PI);
+        let mut rec = HitRecord {
+            p,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            t,
+            u: phi / (2.0 * PI),
+            v: theta / (2.0 * PI),
+            barycentric: None,
+            front_face: false,
+            material: self.material.clone(),
+        };
+        rec.set_face_normal(r, outward.normalized());
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let e = Vec3::new(outer, self.minor_radius, outer);
+        Some(Aabb::new(self.center - e, self.center + e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vec::Color;
+
+    fn ring() -> Torus {
+        Torus::new(
+            Point3::new(1.0, 2.0, 3.0),
+            2.0,
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_hit() {
+        let torus = ring();
+        // along x through the middle: outer wall at 2.5 from the center
+        let r = Ray::new(Point3::new(11.0, 2.0, 3.0), Vec3::new(-2.0, 0.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.75).abs() < 1e-9);
+        assert!(rec.front_face && (rec.normal.x() - 1.0).abs() < 1e-9);
+        assert!(rec.u.abs() < 1e-9 && rec.v.abs() < 1e-9);
+        // the next wall is the inside of the hole, facing -x
+        let rec = torus.hit(&r, 3.8, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.25).abs() < 1e-9 && !rec.front_face);
+        assert!((rec.v - 0.5).abs() < 1e-9);
+
+        // down through the hole, and down onto the top of the tube
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!(torus
+            .hit(
+                &Ray::new(Point3::new(1.0, 5.0, 3.0), down, 0.0),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        let rec = torus
+            .hit(
+                &Ray::new(Point3::new(1.0, 5.0, 5.0), down, 0.0),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-9 && (rec.normal.y() - 1.0).abs() < 1e-9);
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_far_origin() {
+        // grazing the top of the tube from far away stays on the surface
+        let torus = ring();
+        let origin = Point3::new(-1.0e5, 2.4, 3.0);
+        let target = Point3::new(1.0, 2.4, 3.0);
+        let r = Ray::new(origin, target - origin, 0.0);
+        let rec = torus.hit(&r, 0.001, f64::INFINITY).unwrap();
+        let local = rec.p - Point3::new(1.0, 2.0, 3.0);
+        let tube = ((local.x() * local.x() + local.z() * local.z()).sqrt() - 2.0).hypot(local.y());
+        assert!((tube - 0.5).abs() < 1e-9);
+        assert!(rec.p.x() < -1.0);
+    }
+}
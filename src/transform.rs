@@ -0,0 +1,282 @@
+//! Affine transforms for placing objects.
+
+use std::ops::Mul;
+
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// Row-major 4x4 matrix acting on column vectors.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        Mat4::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Right-handed: counter-clockwise seen with `axis` pointing at the
+    /// viewer. `axis` need not be unit length.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let a = axis.normalized();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+        Mat4::new([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` if singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4::new(inv))
+    }
+
+    //affine only, the bottom row is ignored
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let row = |i: usize| self.m[i][0] * v.x() + self.m[i][1] * v.y() + self.m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+/// An invertible matrix kept together with its inverse.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    /// `None` if `matrix` has no inverse.
+    pub fn new(matrix: Mat4) -> Option<Transform> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        Transform {
+            matrix: Mat4::translation(offset),
+            inverse: Mat4::translation((-1.0) * offset),
+        }
+    }
+
+    /// `None` if a factor is zero.
+    pub fn scale(factors: Vec3) -> Option<Transform> {
+        Transform::new(Mat4::scaling(factors))
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let matrix = Mat4::rotation(axis, degrees);
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Normals go through the inverse transpose to stay perpendicular to
+    /// the surface; the result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// Keeps t: `ray(r).at(t)` is the image of `r.at(t)`.
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::new(self.point(r.origin()), self.vector(r.direction()), r.time())
+    }
+
+    /// Box around the eight transformed corners of `bbox`.
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        let (min, max) = (bbox.min(), bbox.max());
+        let corner = |i: usize| {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    min[axis]
+                } else {
+                    max[axis]
+                }
+            };
+            self.point(Vec3::new(pick(0), pick(1), pick(2)))
+        };
+        (1..8).fold(Aabb::new(corner(0), corner(0)), |acc, i| {
+            Aabb::surrounding(acc, Aabb::new(corner(i), corner(i)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = Mat4::new([
+            [2.0, 0.0, 1.0, 3.0],
+            [1.0, 1.0, 0.0, -1.0],
+            [0.0, 3.0, 1.0, 2.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let product = m * m.inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.get(i, j) - expected).abs() < 1e-12);
+            }
+        }
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn test_compose() {
+        // a quarter turn about y takes +x to -z, then move up
+        let t = Transform::rotate(Vec3::new(0.0, 2.0, 0.0), 90.0)
+            .then(&Transform::translate(Vec3::new(0.0, 1.0, 0.0)));
+        let p = t.point(Point3::new(1.0, 0.0, 0.0));
+        assert!(close(p, Point3::new(0.0, 1.0, -1.0)));
+        assert!(close(t.inverse().point(p), Point3::new(1.0, 0.0, 0.0)));
+        assert!(close(
+            t.vector(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 0.0, -1.0)
+        ));
+
+        let bbox = t.bounding_box(Aabb::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 1.0, 1.0),
+        ));
+        assert!(close(bbox.min(), Point3::new(0.0, 1.0, -2.0)));
+        assert!(close(bbox.max(), Point3::new(1.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn test_normal_under_squash() {
+        // the plane x + y = 1 squashed along y: its normal must stay
+        // perpendicular to the squashed surface
+        let t = Transform::scale(Vec3::new(1.0, 0.25, 1.0)).unwrap();
+        let tangent = t.vector(Vec3::new(1.0, -1.0, 0.0));
+        let normal = t.normal(Vec3::new(1.0, 1.0, 0.0));
+        assert!(tangent.dot(normal).abs() < 1e-12);
+        assert!(Transform::scale(Vec3::new(0.0, 1.0, 1.0)).is_none());
+    }
+}
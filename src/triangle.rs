@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use crate::material::Scatter;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::light::Light;
+use super::random::random_double;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+//relative to |dir| |e1| |e2|, which det scales with
+const EPSILON: f64 = 1.0e-12;
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    double_sided: bool,
+    material: Arc<dyn Scatter>,
+}
+
+impl Triangle {
+    /// Counter-clockwise vertices face the viewer. Double-sided by default.
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, m: Arc<dyn Scatter>) -> Triangle {
+        Triangle {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            double_sided: true,
+            material: m,
+        }
+    }
+
+    //per-vertex shading normals
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Triangle {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Triangle {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    //single-sided triangles are invisible from the back
+    pub fn double_sided(mut self, double_sided: bool) -> Triangle {
+        self.double_sided = double_sided;
+        self
+    }
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+/// Returns (t, b1, b2), the weight of v0 is 1 - b1 - b2.
+pub fn intersect(
+    r: &Ray,
+    vertices: [Point3; 3],
+    t_min: f64,
+    t_max: f64,
+    cull_backface: bool,
+) -> Option<(f64, f64, f64)> {
+    let e1 = vertices[1] - vertices[0];
+    let e2 = vertices[2] - vertices[0];
+    let pvec = r.direction().cross(e2);
+    let det = e1.dot(pvec);
+    let tolerance = EPSILON * r.direction().length() * e1.length() * e2.length();
+
+    // det < 0: ray hits the back face
+    if cull_backface {
+        if det < tolerance {
+            return None;
+        }
+    } else if det.abs() < tolerance {
+        //parallel to the plane
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - vertices[0];
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(e1);
+    let b2 = r.direction().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(qvec) * inv_det;
+    if t < t_max && t > t_min {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+/// Fills in normal, uv and barycentrics for a hit (t, b1, b2) from `intersect`.
+pub fn hit_record(
+    r: &Ray,
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    (t, b1, b2): (f64, f64, f64),
+    material: Arc<dyn Scatter>,
+) -> HitRecord {
+    let b0 = 1.0 - b1 - b2;
+    let (u, v) = match uvs {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1, b2),
+    };
+    let mut rec = HitRecord {
+        p: r.at(t),
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        t,
+        u,
+        v,
+        barycentric: Some((b1, b2)),
+        front_face: false,
+        material,
+    };
+
+    //front face is decided by the geometric normal
+    let geometric = (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .normalized();
+    rec.set_face_normal(r, geometric);
+
+    if let Some(n) = normals {
+        let shading = (b0 * n[0] + b1 * n[1] + b2 * n[2]).normalized();
+        //keep the shading normal on the same side as the geometric one
+        rec.normal = if shading.dot(rec.normal) < 0.0 {
+            -1.0 * shading
+        } else {
+            shading
+        };
+    }
+    rec
+}
+
+pub fn bounding_box(vertices: [Point3; 3]) -> Aabb {
+    //pad so axis-aligned triangles don't give a flat box
+    const PAD: f64 = 1.0e-4;
+    let mut bbox = Aabb::new(vertices[0], vertices[1]);
+    bbox = Aabb::surrounding(bbox, Aabb::new(vertices[2], vertices[2]));
+    let mut min = bbox.min();
+    let mut max = bbox.max();
+    for a in 0..3 {
+        if max[a] - min[a] < PAD {
+            min[a] -= PAD / 2.0;
+            max[a] += PAD / 2.0;
+        }
+    }
+    Aabb::new(min, max)
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let hit = intersect(r, self.vertices, t_min, t_max, !self.double_sided)?;
+        Some(hit_record(
+            r,
+            self.vertices,
+            self.normals,
+            self.uvs,
+            hit,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounding_box(self.vertices))
+    }
+}
+
+//uniform over the area, converted to solid angle at `origin`
+impl Light for Triangle {
+    fn sample_light(&self, origin: Point3) -> Option<(Vec3, f64)> {
+        //sqrt warps the unit square evenly onto the triangle
+        let su = random_double().sqrt();
+        let xi = random_double();
+        let (b1, b2) = (su * (1.0 - xi), su * xi);
+        let [v0, v1, v2] = self.vertices;
+        let point = v0 + b1 * (v1 - v0) + b2 * (v2 - v0);
+        let direction = (point - origin).normalized();
+        let pdf = self.pdf(origin, direction);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+
+    fn pdf(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction, 0.0);
+        let Some((t, _, _)) = intersect(&r, self.vertices, 0.001, f64::INFINITY, false) else {
+            return 0.0;
+        };
+        let [v0, v1, v2] = self.vertices;
+        let n = (v1 - v0).cross(v2 - v0);
+        let area = 0.5 * n.length();
+        let distance = t * direction.length();
+        let cosine = (direction.dot(n) / (direction.length() * n.length())).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance * distance / (cosine * area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vec::Color;
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_barycentric() {
+        let tri = unit_triangle();
+        let r = Ray::new(Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = tri.hit(&r, 0.001, f64::INFINITY).unwrap();
+        let (b1, b2) = rec.barycentric.unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((b1 - 0.25).abs() < 1e-12);
+        assert!((b2 - 0.5).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal.z() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_miss_outside() {
+        let tri = unit_triangle();
+        let r = Ray::new(Point3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(tri.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_sidedness() {
+        let r = Ray::new(Point3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = unit_triangle().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.front_face);
+        assert!((rec.normal.z() - (-1.0)).abs() < 1e-12);
+        assert!(unit_triangle()
+            .double_sided(false)
+            .hit(&r, 0.001, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn test_small_scale() {
+        // a triangle 1e-6 across with a unit ray, and the unit triangle with
+        // a direction 1e-6 long
+        let tiny = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0e-6, 0.0, 0.0),
+            Point3::new(0.0, 1.0e-6, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(
+            Point3::new(0.25e-6, 0.5e-6, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let rec = tiny.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        let (b1, b2) = rec.barycentric.unwrap();
+        assert!((b1 - 0.25).abs() < 1e-9 && (b2 - 0.5).abs() < 1e-9);
+
+        let r = Ray::new(
+            Point3::new(0.25, 0.5, 1.0),
+            Vec3::new(0.0, 0.0, -1.0e-6),
+            0.0,
+        );
+        let rec = unit_triangle().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0e6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_interpolated_uv() {
+        let tri = unit_triangle().with_uvs([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        let r = Ray::new(Point3::new(0.5, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = tri.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.u - 0.75).abs() < 1e-12);
+        assert!((rec.v - 0.25).abs() < 1e-12);
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Range, Sub, SubAssign},
 };
 
-use rand::Rng;
+use crate::random::random_range;
 
 #[derive(Clone, Copy)]
 pub struct Vec3 {
@@ -48,30 +48,12 @@ impl Vec3 {
     pub fn normalized(self) -> Vec3 {
         self / self.length()
     }
-    // 0,1 to 0,255
-    pub fn format_color(self, samples_per_pixel: u64) -> String {
-        let ir = (256.0
-            * (self[0] / (samples_per_pixel as f64))
-                .sqrt()
-                .clamp(0.0, 0.999)) as u64;
-        let ig = (256.0
-            * (self[1] / (samples_per_pixel as f64))
-                .sqrt()
-                .clamp(0.0, 0.999)) as u64;
-        let ib = (256.0
-            * (self[2] / (samples_per_pixel as f64))
-                .sqrt()
-                .clamp(0.0, 0.999)) as u64;
-        format!("{} {} {}", ir, ig, ib)
-    }
-
     pub fn random(r: Range<f64>) -> Vec3 {
-        let mut rng = rand::thread_rng();
         Vec3 {
             e: [
-                rng.gen_range(r.clone()),
-                rng.gen_range(r.clone()),
-                rng.gen_range(r.clone()),
+                random_range(r.clone()),
+                random_range(r.clone()),
+                random_range(r.clone()),
             ],
         }
     }
@@ -85,6 +67,11 @@ impl Vec3 {
         }
     }
 
+    //uniform on the unit sphere surface
+    pub fn random_unit_vector() -> Vec3 {
+        Self::random_in_unit_sphere().normalized()
+    }
+
     pub fn random_in_hemisphere(normal: Vec3) -> Vec3 {
         let in_unit_sphere = Self::random_in_unit_sphere();
         if in_unit_sphere.dot(normal) > 0.0 {
@@ -94,6 +81,17 @@ impl Vec3 {
             (-1.0) * in_unit_sphere
         }
     }
+    //two unit vectors completing the unit vector self to a right-handed basis
+    pub fn orthonormal_basis(self) -> (Vec3, Vec3) {
+        let a = if self.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = self.cross(a).normalized();
+        (v.cross(self), v)
+    }
+
     pub fn near_zero(self) -> bool {
         const EPS: f64 = 1.0e-8;
         self[0].abs() < EPS && self[1].abs() < EPS && self[2].abs() < EPS
@@ -111,10 +109,8 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = rand::thread_rng();
-
         loop {
-            let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+            let p = Vec3::new(random_range(-1.0..1.0), random_range(-1.0..1.0), 0.0);
             if p.length() < 1.0 {
                 return p;
             }